@@ -2,6 +2,8 @@ use std::{ffi::OsString, path::PathBuf};
 
 use clap::{Parser, Subcommand};
 
+use crate::specs::ConflictPolicy;
+
 /// tmpl is a barebones command line tool for generating multiple templated files from a single
 /// TOML spec.
 #[derive(Parser)]
@@ -19,6 +21,7 @@ pub enum Commands {
     Edit(EditArgs),
     Rm(RmArgs),
     Cp(CpArgs),
+    Undo(UndoArgs),
 }
 
 /// List specs in the specs directory
@@ -42,24 +45,71 @@ pub struct NewArgs {
 }
 
 /// Generate templates from a spec, with options if specified in your spec file
-#[derive(Debug, clap::Args)]
+#[derive(Debug, Default, clap::Args)]
 pub struct GenArgs {
-    /// The spec's name. This is mutually exclusive with --file, and will result in an error if
-    /// both are used
-    pub name: Option<OsString>,
+    /// The spec's name. Several specs can be generated together, as if they were one spec. This is
+    /// mutually exclusive with --file, and will result in an error if both are used
+    pub names: Vec<OsString>,
 
-    /// Options as key-value pairs (can be specified multiple times)
+    /// Options as key-value pairs (can be specified multiple times). Add a type to the key to force
+    /// it, e.g. -o version:str 1.10
     #[arg(short = 'o', value_names = ["KEY", "VALUE"], num_args = 2)]
     pub options: Vec<String>,
 
+    /// Read variables from a TOML, JSON or YAML file (can be specified multiple times). They
+    /// override the spec's defaults, and are overridden by -o
+    #[arg(long = "vars-file", value_name = "FILE")]
+    pub vars_files: Vec<PathBuf>,
+
+    /// The prefix of environment variables that set variables, e.g. TMPL_VAR_project=demo. They
+    /// override variables files, and are overridden by -o [default: TMPL_VAR_]
+    #[arg(long = "env-prefix", value_name = "PREFIX")]
+    pub env_prefix: Option<String>,
+
     /// The directory to generate the spec in.
     #[arg(short = 'c')]
     pub workdir: Option<PathBuf>,
 
     /// Point to a spec file, instead of using a spec in the spec directory. Mutually exclusive
-    /// with NAME.
+    /// with NAMES.
     #[arg(short = 'f', long = "file")]
     pub spec_file: Option<PathBuf>,
+
+    /// Render every template and report what would be written, without touching the disk
+    #[arg(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Show a unified diff against existing files. Only valid with --dry-run
+    #[arg(long, requires = "dry_run")]
+    pub diff: bool,
+
+    /// What to do with files that already exist. A template's own on_conflict takes precedence
+    /// [default: overwrite]
+    #[arg(long = "on-conflict", value_enum)]
+    pub on_conflict: Option<ConflictPolicy>,
+
+    /// Write every file or none of them. Nothing is written if any template fails, and files that
+    /// were already replaced are restored if a write fails
+    #[arg(long)]
+    pub atomic: bool,
+
+    /// Ask for every variable that wasn't given with -o, suggesting the spec's default
+    #[arg(long, conflicts_with = "no_input")]
+    pub prompt: bool,
+
+    /// Never ask for variables, even ones without a value
+    #[arg(long = "no-input")]
+    pub no_input: bool,
+
+    /// Run the spec's hooks without asking first. Without it, hooks are only run if you agree to
+    /// them on the terminal
+    #[arg(long = "run-hooks")]
+    pub run_hooks: bool,
+
+    /// Generate without running the spec's hooks. Without it, a spec with pre-generation hooks
+    /// that aren't run isn't generated at all
+    #[arg(long = "skip-hooks", conflicts_with = "run_hooks")]
+    pub skip_hooks: bool,
 }
 
 /// Open a spec in your editor of choice
@@ -92,3 +142,12 @@ pub struct CpArgs {
     #[arg(short = 'y', long = "yes", default_value_t = false, action = clap::ArgAction::SetTrue)]
     pub skip_prompt: bool,
 }
+
+/// Undo the last gen in a directory. Created files are removed, and modified files are restored,
+/// unless they've changed since they were generated
+#[derive(Debug, Default, clap::Args)]
+pub struct UndoArgs {
+    /// The directory the spec was generated in.
+    #[arg(short = 'c')]
+    pub workdir: Option<PathBuf>,
+}
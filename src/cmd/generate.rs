@@ -1,22 +1,44 @@
 /// gen is a reserved keyword, that's why this module doesn't match the other's naming convention.
-use anyhow::{Context, Result, bail};
-use minijinja::Environment;
+use anyhow::{Context, Result, bail, ensure};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use minijinja::{Environment, Value, value::merge_maps};
+use similar::TextDiff;
 use std::{
     env::set_current_dir,
+    ffi::OsString,
+    fmt,
     fs::{self, create_dir_all, write},
-    io::Write,
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
 };
-use toml::value::Datetime;
 
 use crate::cli::GenArgs;
 
-use crate::{io::IO, path::check_path_is_valid, specs::Specs};
+use crate::{
+    computed, filters, functions, hooks,
+    inject::inject,
+    io::IO,
+    manifest::Manifest,
+    path::{backup_path, check_path_is_valid},
+    prompt::{prompt_value, prompt_yn},
+    schema::{self, Schema},
+    specs::{ConflictPolicy, Hook, Specs, Template, compose, merge_tables, read_spec_dir},
+    transaction::Transaction,
+    vars::{self, set_path},
+};
 
 /// Merge options specified by the user through the command line, with variables defined in their
 /// spec. The command line option is added to this map if it doesn't already exist, otherwise, it
 /// overwrites the variable defined in the spec. This gives the user the ability to define defaults
-/// in the spec, but easily override them when generating the spec from the command line.
-fn merge_options(defaults: &toml::Table, options: Vec<String>) -> toml::Table {
+/// in the spec, but easily override them when generating the spec from the command line. Keys
+/// with dots in them set a key in a nested table, e.g. db.port. A value keeps the type of the
+/// variable's default, or the type declared in its schema, unless the key has a type annotation,
+/// like version:str.
+fn merge_options(
+    defaults: &toml::Table,
+    schema: &Schema,
+    options: Vec<String>,
+) -> Result<toml::Table> {
     let mut variables = defaults.clone();
 
     let chunks = options.chunks(2).filter_map(|chunk| {
@@ -28,18 +50,507 @@ fn merge_options(defaults: &toml::Table, options: Vec<String>) -> toml::Table {
     });
 
     for (key, var) in chunks {
-        let value = var
-            .parse::<i64>()
-            .map(toml::Value::Integer)
-            .or_else(|_| var.parse::<f64>().map(toml::Value::Float))
-            .or_else(|_| var.parse::<bool>().map(toml::Value::Boolean))
-            .or_else(|_| var.parse::<Datetime>().map(toml::Value::Datetime))
-            .unwrap_or_else(|_| toml::Value::String(var.clone()));
+        let (name, kind) = vars::split_annotation(&key);
+        let value = match kind {
+            Some(kind) => vars::coerce(&var, kind).context(format!("Unable to set {}", name))?,
+            None => vars::parse_like(
+                &var,
+                vars::get_path(defaults, name),
+                vars::declared_type(schema, name),
+            ),
+        };
+
+        set_path(&mut variables, name, value);
+    }
+
+    Ok(variables)
+}
+
+/// Ask the user for the values of variables on the terminal. Variables without a value, meaning an
+/// empty string in the spec or a schema definition without a default, are always asked for. When
+/// prompt_all is set, so is every variable that wasn't given on the command line. The current value
+/// is suggested as the default answer.
+fn prompt_for_variables(
+    variables: &mut toml::Table,
+    schema: &Schema,
+    given: &[String],
+    prompt_all: bool,
+) -> Result<()> {
+    let mut names: Vec<String> = variables.keys().cloned().collect();
+    names.extend(
+        schema
+            .keys()
+            .filter(|name| !variables.contains_key(*name))
+            .cloned(),
+    );
+
+    for name in names {
+        if given.contains(&name) {
+            continue;
+        }
+
+        let current = match variables.get(&name) {
+            Some(toml::Value::String(s)) => s.clone(),
+            Some(other) => other.to_string(),
+            None => String::new(),
+        };
+
+        if !prompt_all && !current.is_empty() {
+            continue;
+        }
+
+        let question = match schema.get(&name).and_then(|d| d.description.as_ref()) {
+            Some(description) => format!("{} ({})", name, description),
+            None => name.clone(),
+        };
+
+        let suggestion = (!current.is_empty()).then_some(current.as_str());
+        let answer =
+            prompt_value(&question, suggestion).context(format!("Unable to ask for {}", name))?;
+
+        if answer != current {
+            let value = vars::parse_like(
+                &answer,
+                variables.get(&name),
+                vars::declared_type(schema, &name),
+            );
+            variables.insert(name, value);
+        }
+    }
+
+    Ok(())
+}
+
+/// Expand a template into the contexts it should be rendered with. A for_each template is rendered
+/// once per element of the list its expression evaluates to, with the element bound to its `as`
+/// name. Every other template is rendered once, with the context as-is.
+fn expand(env: &Environment, template: &Template, context: &Value) -> Result<Vec<Value>> {
+    let Some(for_each) = &template.for_each else {
+        return Ok(vec![context.clone()]);
+    };
+
+    let items = env
+        .compile_expression(for_each)
+        .and_then(|expr| expr.eval(context))
+        .context(format!("Unable to evaluate for_each = \"{}\"", for_each))?;
+
+    let name = template.item_name.as_deref().unwrap_or("item");
+
+    let contexts = items
+        .try_iter()
+        .context(format!("for_each = \"{}\" is not a list", for_each))?
+        .map(|item| merge_maps([context.clone(), Value::from_iter([(name, item)])]))
+        .collect();
+
+    Ok(contexts)
+}
+
+/// Evaluate a when expression against a context, to decide whether a template should be generated,
+/// or a hook run, at all. Templates and hooks without one always are.
+fn is_enabled(env: &Environment, when: Option<&str>, context: &Value) -> Result<bool> {
+    let Some(when) = when else {
+        return Ok(true);
+    };
+
+    let value = env
+        .compile_expression(when)
+        .and_then(|expr| expr.eval(context))
+        .context(format!("Unable to evaluate when = \"{}\"", when))?;
+
+    Ok(value.is_true())
+}
+
+/// Join the names of several specs, for reporting on them as a whole.
+fn join_names(names: &[OsString]) -> OsString {
+    let mut joined = OsString::new();
+
+    for (i, name) in names.iter().enumerate() {
+        if i > 0 {
+            joined.push(", ");
+        }
+        joined.push(name);
+    }
+
+    joined
+}
+
+/// Render a hook into the command it runs, or nothing if its when expression is falsy.
+fn plan_hook(env: &Environment, hook: &Hook, context: &Value) -> Result<Option<Vec<String>>> {
+    if !is_enabled(env, hook.when.as_deref(), context)? {
+        return Ok(None);
+    }
+
+    hooks::render(env, hook, context).map(Some)
+}
+
+/// Decide whether to run a spec's hooks. A spec can come from anyone, so they're only run when
+/// the user opts in with --run-hooks, or agrees to the exact commands on the terminal.
+fn trust_hooks<Stdout: Write, Stderr: Write>(
+    commands: &[Vec<String>],
+    run_hooks: bool,
+    interactive: bool,
+    io: &mut IO<Stdout, Stderr>,
+) -> Result<bool> {
+    if run_hooks {
+        return Ok(true);
+    }
+
+    if !interactive {
+        return Ok(false);
+    }
+
+    writeln!(io.stderr(), "This spec runs the following commands:")
+        .context("Failed to write hook summary to stderr writer")?;
+
+    for command in commands {
+        writeln!(io.stderr(), "\t{}", hooks::display(command))
+            .context("Failed to write hook summary to stderr writer")?;
+    }
+
+    prompt_yn("Do you want to run them?", false).context("Do you want to run hooks prompt")
+}
+
+/// The directory a spec's source files are relative to. That's the spec itself for a directory
+/// spec, or the directory a single file spec is in.
+fn spec_root(path: &Path) -> Result<PathBuf> {
+    let path = fs::canonicalize(path).context(format!("Unable to find {}", path.display()))?;
+
+    if path.is_dir() {
+        Ok(path)
+    } else {
+        Ok(path.parent().map(Path::to_path_buf).unwrap_or_default())
+    }
+}
+
+/// Produce the contents of a template's file. Sources and base64 encoded contents are copied
+/// byte-for-byte, and raw bodies are used as-is. Everything else is rendered through minijinja.
+fn render_contents(
+    env: &Environment,
+    template: &Template,
+    name: &str,
+    context: &Value,
+    root: &Path,
+) -> Result<Vec<u8>> {
+    match (&template.source, &template.base64) {
+        (Some(_), Some(_)) => bail!("A template can't have both a source and base64 contents"),
+        (Some(source), None) => {
+            check_path_is_valid(source)?;
+            fs::read(root.join(source)).context(format!("Unable to read {}", source.display()))
+        }
+        (None, Some(encoded)) => {
+            let encoded: String = encoded.split_whitespace().collect();
+            STANDARD
+                .decode(encoded)
+                .context("Unable to decode base64 contents")
+        }
+        (None, None) if template.raw => Ok(template.body.clone().into_bytes()),
+        (None, None) => {
+            let rendered = env
+                .template_from_named_str(name, &template.body)?
+                .render(context)?;
+            Ok(rendered.into_bytes())
+        }
+    }
+}
+
+/// Render a template's path through minijinja, so paths can be built from variables, e.g.
+/// src/{{ crate_name }}/lib.rs.
+fn render_path(env: &Environment, path: &Path, context: &Value) -> Result<PathBuf> {
+    let source = path
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("Invalid UTF-8 in template path"))?;
+
+    let rendered = env
+        .render_str(source, context)
+        .context(format!("Unable to render path {}", path.display()))?;
+
+    Ok(PathBuf::from(rendered))
+}
+
+/// Status describes what writing a rendered template would do to the file that's already at its
+/// path, if there is one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    New,
+    Unchanged,
+    Overwrite,
+    Inject,
+}
+
+impl Status {
+    /// Compare the rendered contents of a template with whatever currently exists at its path.
+    fn of(path: &Path, contents: &[u8]) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Status::New);
+        }
+
+        let existing =
+            fs::read(path).context(format!("Unable to read existing {}", path.display()))?;
+
+        if existing == contents {
+            Ok(Status::Unchanged)
+        } else {
+            Ok(Status::Overwrite)
+        }
+    }
+
+    /// Compare the result of injecting into a file with its original contents. Injecting isn't
+    /// considered a conflict, the file is meant to be modified.
+    fn of_injection(existing: Option<&[u8]>, contents: &[u8]) -> Self {
+        match existing {
+            None => Status::New,
+            Some(existing) if existing == contents => Status::Unchanged,
+            Some(_) => Status::Inject,
+        }
+    }
+}
+
+/// Read the file at path, if there is one.
+fn read_existing(path: &Path) -> Result<Option<Vec<u8>>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    fs::read(path)
+        .map(Some)
+        .context(format!("Unable to read existing {}", path.display()))
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Status::New => "new",
+            Status::Unchanged => "unchanged",
+            Status::Overwrite => "would overwrite",
+            Status::Inject => "would inject",
+        };
+        f.pad(s)
+    }
+}
+
+/// Rendered is a template that has been rendered in memory, but hasn't been written to disk yet.
+struct Rendered {
+    path: PathBuf,
+    contents: Vec<u8>,
+    status: Status,
+    policy: ConflictPolicy,
+    mode: Option<u32>,
+    executable: bool,
+}
+
+impl Rendered {
+    /// Write the rendered contents to its path, creating parent directories as needed.
+    fn write(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            create_dir_all(parent)?;
+        }
+
+        write(&self.path, &self.contents)?;
+        self.set_permissions()
+    }
+
+    /// Apply the template's mode and executable bit to the written file. Executable adds execute
+    /// permission for everyone that can read the file.
+    #[cfg(unix)]
+    fn set_permissions(&self) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        if self.mode.is_none() && !self.executable {
+            return Ok(());
+        }
+
+        let mut mode = match self.mode {
+            Some(mode) => mode,
+            None => fs::metadata(&self.path)?.permissions().mode(),
+        };
+
+        if self.executable {
+            mode |= (mode & 0o444) >> 2;
+        }
+
+        fs::set_permissions(&self.path, fs::Permissions::from_mode(mode)).context(format!(
+            "Unable to set the permissions of {}",
+            self.path.display()
+        ))
+    }
+
+    /// File modes are a unix concept, so they're ignored everywhere else.
+    #[cfg(not(unix))]
+    fn set_permissions(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Parse an octal file mode, like 0755 or 644.
+fn parse_mode(mode: &str) -> Result<u32> {
+    let digits = mode.strip_prefix("0o").unwrap_or(mode);
+    let parsed = u32::from_str_radix(digits, 8)
+        .context(format!("{} is not a valid octal file mode", mode))?;
+
+    ensure!(parsed <= 0o7777, "{} is not a valid file mode", mode);
+    Ok(parsed)
+}
+
+/// Write every rendered template, and backup, in a single transaction, so either all of them are
+/// written, or none of them are.
+fn write_atomically(rendered: &[&Rendered], backups: &[Backup]) -> Result<()> {
+    let mut transaction = Transaction::new(Path::new("."))?;
+
+    for b in backups {
+        transaction.stage(&b.path, &b.contents)?;
+    }
+
+    for r in rendered {
+        transaction.stage(&r.path, &r.contents)?;
+    }
+
+    transaction.commit()
+}
+
+/// Backup is a copy of a file that the backup conflict policy is about to overwrite. It's read up
+/// front, so it can be written along with everything else.
+struct Backup {
+    path: PathBuf,
+    contents: Vec<u8>,
+    permissions: fs::Permissions,
+}
 
-        variables.insert(key, value);
+impl Backup {
+    /// Read the file that's about to be overwritten, picking a backup path that isn't taken.
+    fn of(path: &Path) -> Result<Self> {
+        let contents = fs::read(path).context(format!("Unable to read {}", path.display()))?;
+        let permissions = fs::metadata(path)
+            .context(format!("Unable to read {}", path.display()))?
+            .permissions();
+
+        Ok(Self {
+            path: backup_path(path),
+            contents,
+            permissions,
+        })
+    }
+
+    fn write(&self) -> Result<()> {
+        write(&self.path, &self.contents)
+            .context(format!("Unable to back up to {}", self.path.display()))?;
+        self.set_permissions()
     }
 
-    variables
+    /// Give the backup the same permissions as the file it's a copy of.
+    fn set_permissions(&self) -> Result<()> {
+        fs::set_permissions(&self.path, self.permissions.clone()).context(format!(
+            "Unable to set the permissions of {}",
+            self.path.display()
+        ))
+    }
+}
+
+/// Print a summary of every file that already exists and would be changed, along with the policy
+/// that will be applied to it. If any of them use the fail policy, or the prompt policy without a
+/// terminal to ask on, an error is returned so that nothing gets written.
+fn report_conflicts<Stdout: Write, Stderr: Write>(
+    rendered: &[Rendered],
+    path_prefix: &Path,
+    interactive: bool,
+    io: &mut IO<Stdout, Stderr>,
+) -> Result<()> {
+    let conflicts: Vec<_> = rendered
+        .iter()
+        .filter(|r| r.status == Status::Overwrite)
+        .collect();
+
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(io.stderr(), "The following files already exist:")
+        .context("Failed to write conflict summary to stderr writer")?;
+
+    for r in &conflicts {
+        writeln!(
+            io.stderr(),
+            "\t{} ({})",
+            path_prefix.join(&r.path).display(),
+            r.policy.as_str()
+        )
+        .context("Failed to write conflict summary to stderr writer")?;
+    }
+
+    let failed = conflicts
+        .iter()
+        .filter(|r| r.policy == ConflictPolicy::Fail)
+        .count();
+
+    ensure!(
+        failed == 0,
+        "{} file(s) already exist and are set to fail on conflict, nothing was generated",
+        failed
+    );
+
+    let unanswerable = conflicts
+        .iter()
+        .filter(|r| r.policy == ConflictPolicy::Prompt)
+        .count();
+
+    ensure!(
+        interactive || unanswerable == 0,
+        "{} file(s) already exist and are set to prompt on conflict, but there's no terminal to \
+        ask on, nothing was generated",
+        unanswerable
+    );
+
+    Ok(())
+}
+
+/// Apply a rendered template's conflict policy, returning whether it should be written. Files that
+/// don't conflict are always written. Backups aren't made here, they're written with the files.
+fn resolve_conflict(r: &Rendered) -> Result<bool> {
+    if r.status != Status::Overwrite {
+        return Ok(true);
+    }
+
+    match r.policy {
+        ConflictPolicy::Overwrite | ConflictPolicy::Backup => Ok(true),
+        ConflictPolicy::Skip | ConflictPolicy::Fail => Ok(false),
+        ConflictPolicy::Prompt => {
+            let question = format!("{} exists, do you want to overwrite it?", r.path.display());
+            prompt_yn(&question, false).context("Do you wish to overwrite prompt")
+        }
+    }
+}
+
+/// Report what a generation run would do, without writing anything. When diff is set, a unified
+/// diff is printed for every file that would be overwritten.
+fn report<Stdout: Write, Stderr: Write>(
+    rendered: &[Rendered],
+    path_prefix: &Path,
+    diff: bool,
+    io: &mut IO<Stdout, Stderr>,
+) -> Result<()> {
+    for r in rendered {
+        let display = path_prefix.join(&r.path);
+        writeln!(io.stdout(), "{:<15} {}", r.status, display.display())
+            .context("Failed to write dry run status to stdout writer")?;
+
+        if diff && matches!(r.status, Status::Overwrite | Status::Inject) {
+            let existing = read_existing(&r.path)?.unwrap_or_default();
+
+            // the headers are relative, like a git patch, the status line shows where it is
+            let a = format!("a/{}", r.path.display());
+            let b = format!("b/{}", r.path.display());
+
+            match (str::from_utf8(&existing), str::from_utf8(&r.contents)) {
+                (Ok(old), Ok(new)) => {
+                    let text_diff = TextDiff::from_lines(old, new);
+                    write!(io.stdout(), "{}", text_diff.unified_diff().header(&a, &b))
+                }
+                _ => writeln!(io.stdout(), "Binary files {} and {} differ", a, b),
+            }
+            .context("Failed to write diff to stdout writer")?;
+        }
+    }
+
+    Ok(())
 }
 
 /// generate corresponds to the gen subcommand. It generates the given template spec
@@ -49,39 +560,134 @@ pub fn generate<Stdout: Write, Stderr: Write>(
     io: &mut IO<Stdout, Stderr>,
 ) -> Result<()> {
     // gotta have one or the other, but not both nor neither
-    let (spec, spec_name) = match (&args.name, &args.spec_file) {
-        (Some(name), None) => {
+    let (spec, spec_name, root) = match (args.names.as_slice(), &args.spec_file) {
+        ([name], None) => {
             let spec = specs
                 .read_spec(name)
                 .context("Unable to parse template file")?;
-            (spec, name.clone())
+            (spec, name.clone(), spec_root(&specs.dir().join(name))?)
+        }
+        ([], Some(path)) if path.is_dir() => {
+            let spec = read_spec_dir(path)
+                .and_then(|spec| specs.resolve(spec))
+                .context(format!("Unable to read spec directory {}", path.display()))?;
+            (spec, path.clone().into_os_string(), spec_root(path)?)
         }
-        (None, Some(path)) => {
+        ([], Some(path)) => {
             let contents =
                 fs::read_to_string(path).context(format!("Unable to read {}", path.display()))?;
             let spec = toml::from_str(&contents)
                 .context(format!("Unable to deserialize {}", path.display()))?;
-            (spec, path.clone().into_os_string())
+            let spec = specs
+                .resolve(spec)
+                .context(format!("Unable to read {}", path.display()))?;
+            (spec, path.clone().into_os_string(), spec_root(path)?)
         }
-        (None, None) => {
+        ([], None) => {
             bail!(
                 "Either the name of a spec in the spec directory, or the path to a spec file (--file) is required."
             )
         }
-        (Some(_), Some(_)) => {
+        (_, Some(_)) => {
             bail!("A spec name and a path to a spec file cannot be given at the same time")
         }
+        (names, None) => {
+            let mut parts = Vec::new();
+            for name in names {
+                let mut spec = specs
+                    .read_spec(name)
+                    .context(format!("Unable to parse {}", name.display()))?;
+
+                // each spec's sources stay relative to its own directory
+                let root = spec_root(&specs.dir().join(name))?;
+                for t in &mut spec.templates {
+                    t.root.get_or_insert_with(|| root.clone());
+                }
+
+                parts.push((name.clone(), spec));
+            }
+
+            let spec = compose(parts).context("Unable to generate these specs together")?;
+            (spec, join_names(names), specs.dir())
+        }
     };
 
+    // read before changing directory, since their paths are relative to where tmpl was run
+    let vars_files = args
+        .vars_files
+        .iter()
+        .map(|path| vars::read_file(path))
+        .collect::<Result<Vec<_>>>()?;
+
     if let Some(path) = &args.workdir {
         set_current_dir(path).context("Unable to change the current working directory")?;
     }
 
+    // from minijinja, with tmpl's own filters and functions on top
+    let mut env = Environment::new();
+    filters::register(&mut env);
+    functions::register(&mut env);
+
+    // shared partials are loaded on demand, when a template includes or imports one
+    let partials = specs.clone();
+    env.set_loader(move |name| {
+        partials.read_partial(name).map_err(|e| {
+            minijinja::Error::new(minijinja::ErrorKind::TemplateNotFound, format!("{:#}", e))
+        })
+    });
+
+    // only prompt when there's someone at a terminal to answer
+    let interactive = !args.no_input && std::io::stdin().is_terminal();
+    let mut given: Vec<_> = args
+        .options
+        .iter()
+        .step_by(2)
+        .map(|key| vars::root_name(key).to_string())
+        .collect();
+
+    // variables files are layered on top of the spec's defaults, in order, then environment
+    // variables on top of those. Both count as given.
+    let prefix = args
+        .env_prefix
+        .as_deref()
+        .unwrap_or(vars::DEFAULT_ENV_PREFIX);
+
+    let mut defaults = spec.defaults();
+    for layer in vars_files {
+        given.extend(layer.keys().cloned());
+        merge_tables(&mut defaults, layer);
+    }
+
+    // environment variables are strings, so they're typed after whatever they're layered on
+    let env_vars = vars::from_env(prefix, &defaults, &spec.schema)?;
+    given.extend(env_vars.keys().cloned());
+    merge_tables(&mut defaults, env_vars);
+
     // Merging options specified by the user with the defaults in their spec.
-    let variables = merge_options(&spec.variables, args.options);
+    let mut variables = merge_options(&defaults, &spec.schema, args.options)?;
+
+    if interactive {
+        prompt_for_variables(&mut variables, &spec.schema, &given, args.prompt)?;
+    }
+
+    // the other variables are validated before computing anything, so every violation is reported
+    // rather than whichever computed variable trips over one first. Computed variables are
+    // validated like any other variable, once they have a value.
+    let (computed_schema, schema): (Schema, Schema) = spec
+        .schema
+        .clone()
+        .into_iter()
+        .partition(|(name, _)| spec.computed.contains_key(name) && !given.contains(name));
+
+    schema::validate(&schema, &variables)
+        .context(format!("Unable to generate {}", spec_name.display()))?;
+
+    computed::evaluate(&env, &spec.computed, &mut variables, &given)
+        .context(format!("Unable to generate {}", spec_name.display()))?;
+
+    schema::validate(&computed_schema, &variables)
+        .context(format!("Unable to generate {}", spec_name.display()))?;
 
-    // from minijinja
-    let mut env = Environment::new();
     let mut errors = Vec::new();
 
     // purely for printing out the names of generated files
@@ -91,30 +697,323 @@ pub fn generate<Stdout: Write, Stderr: Write>(
         "".into()
     };
 
+    // Everything is rendered in memory first, so a dry run can report on it before anything is
+    // written to disk.
+    let mut rendered = Vec::new();
+    let context = vars::context(&variables);
+
+    // hooks are rendered up front, so the user knows exactly what they're agreeing to run. A pre
+    // hook that can't be rendered is as good as a failed precondition.
+    let mut pre_hooks = Vec::new();
+    for hook in &spec.hooks.pre {
+        let command = plan_hook(&env, hook, &context).context(format!(
+            "Unable to render hook {}, nothing was generated",
+            hooks::display(&hook.run)
+        ))?;
+        pre_hooks.extend(command);
+    }
+
+    let mut post_hooks = Vec::new();
+    for hook in &spec.hooks.post {
+        match plan_hook(&env, hook, &context) {
+            Ok(Some(command)) => post_hooks.push(command),
+            Ok(None) => {}
+            Err(e) => errors.push((format!("hook {}", hooks::display(&hook.run)), e)),
+        }
+    }
+
+    let all_hooks: Vec<_> = pre_hooks.iter().chain(&post_hooks).cloned().collect();
+    let run_hooks = !args.dry_run
+        && !args.skip_hooks
+        && !all_hooks.is_empty()
+        && trust_hooks(&all_hooks, args.run_hooks, interactive, io)?;
+
+    // pre hooks are preconditions, so generating without them has to be asked for
+    ensure!(
+        run_hooks || args.dry_run || args.skip_hooks || pre_hooks.is_empty(),
+        "{} pre-generation hook(s) weren't run, nothing was generated. Use --run-hooks to run \
+        them, or --skip-hooks to generate without them",
+        pre_hooks.len()
+    );
+
+    if !run_hooks && !args.dry_run && !all_hooks.is_empty() {
+        writeln!(
+            io.stderr(),
+            "Skipping {} hook(s), use --run-hooks to run them",
+            all_hooks.len()
+        )
+        .context("Failed to write hook summary to stderr writer")?;
+    }
+
+    // pre hooks check preconditions, so the first one to fail stops generation before anything
+    // is rendered or written
+    if run_hooks {
+        for command in &pre_hooks {
+            let display = hooks::display(command);
+            writeln!(io.stderr(), "Running {}", display)
+                .context("Failed to write hook name to stderr writer")?;
+
+            hooks::run(command).context(format!(
+                "Pre-generation hook {} failed, nothing was generated",
+                display
+            ))?;
+        }
+    }
+
+    // for_each templates are expanded up front, so each job renders exactly one file
+    let mut jobs = Vec::new();
     for t in &spec.templates {
-        check_path_is_valid(&t.path)?;
+        match expand(&env, t, &context) {
+            Ok(contexts) => jobs.extend(contexts.into_iter().map(|ctx| (t, ctx))),
+            Err(e) => errors.push((t.path.display().to_string(), e)),
+        }
+    }
+
+    // rendered paths, and the templates they came from, to catch two templates writing one file
+    let mut generated: Vec<(PathBuf, String)> = Vec::new();
+
+    for (t, ctx) in &jobs {
+        match is_enabled(&env, t.when.as_deref(), ctx) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                errors.push((t.path.display().to_string(), e));
+                continue;
+            }
+        }
 
-        let result = (|| -> Result<()> {
-            let name = t
-                .path
+        // the path is a template too, and has to be checked again after it's rendered, since a
+        // variable could smuggle something like ../ into it
+        let path = match render_path(&env, &t.path, ctx) {
+            Ok(path) => path,
+            Err(e) => {
+                errors.push((t.path.display().to_string(), e));
+                continue;
+            }
+        };
+        check_path_is_valid(&path)?;
+
+        if t.inject.is_none() {
+            generated.push((path.clone(), t.path.display().to_string()));
+        }
+
+        let result = (|| -> Result<Rendered> {
+            let name = path
                 .to_str()
                 .ok_or_else(|| anyhow::anyhow!("Invalid UTF-8 in template path"))?;
 
-            env.add_template(name, &t.body)?;
-            let render = env.get_template(name)?.render(&variables)?;
+            // inherited templates keep their sources relative to the spec they came from
+            let source_root = t.root.as_deref().unwrap_or(&root);
+            let mut contents = render_contents(&env, t, name, ctx, source_root)?;
+
+            let status = match &t.inject {
+                Some(how) => {
+                    // an earlier template may have already injected into the same file
+                    let existing = match rendered.iter().rev().find(|r: &&Rendered| r.path == path)
+                    {
+                        Some(r) => Some(r.contents.clone()),
+                        None => read_existing(&path)?,
+                    };
+                    let body = String::from_utf8(contents)
+                        .context("Only text can be injected into a file")?;
+                    let existing_text = existing
+                        .as_deref()
+                        .map(str::from_utf8)
+                        .transpose()
+                        .context("Only text files can be injected into")?;
+                    contents = inject(existing_text, &body, how)?.into_bytes();
+                    Status::of_injection(existing.as_deref(), &contents)
+                }
+                None => Status::of(&path, &contents)?,
+            };
+
+            Ok(Rendered {
+                path: path.clone(),
+                contents,
+                status,
+                policy: t.on_conflict.or(args.on_conflict).unwrap_or_default(),
+                mode: t.mode.as_deref().map(parse_mode).transpose()?,
+                executable: t.executable,
+            })
+        })();
+
+        match result {
+            Ok(r) => rendered.push(r),
+            Err(e) => errors.push((path.display().to_string(), e)),
+        }
+    }
 
-            if let Some(parent) = t.path.parent() {
-                create_dir_all(parent)?;
+    // paths are templates too, so different templates can still end up generating the same file,
+    // and only one of them could be kept
+    let mut collisions = Vec::new();
+    for (i, (path, _)) in generated.iter().enumerate() {
+        // each path is reported once, where it's first generated
+        if generated[..i].iter().any(|(p, _)| p == path) {
+            continue;
+        }
+
+        let templates: Vec<_> = generated
+            .iter()
+            .filter(|(p, _)| p == path)
+            .map(|(_, template)| template.as_str())
+            .collect();
+
+        if templates.len() > 1 {
+            collisions.push((path, templates.join(", ")));
+        }
+    }
+
+    ensure!(
+        collisions.is_empty(),
+        "{} path(s) are generated by more than one template, nothing was generated:\n\t{}",
+        collisions.len(),
+        collisions
+            .iter()
+            .map(|(path, templates)| format!("{} ({})", path.display(), templates))
+            .collect::<Vec<_>>()
+            .join("\n\t")
+    );
+
+    // in atomic mode, a single template failing to render means nothing is written
+    let abort = args.atomic && !errors.is_empty();
+
+    if args.dry_run {
+        for command in &pre_hooks {
+            writeln!(
+                io.stdout(),
+                "{:<15} {}",
+                "would run",
+                hooks::display(command)
+            )
+            .context("Failed to write dry run status to stdout writer")?;
+        }
+
+        report(&rendered, &path_prefix, args.diff, io)?;
+
+        for command in &post_hooks {
+            writeln!(
+                io.stdout(),
+                "{:<15} {}",
+                "would run",
+                hooks::display(command)
+            )
+            .context("Failed to write dry run status to stdout writer")?;
+        }
+    } else if !abort {
+        report_conflicts(&rendered, &path_prefix, interactive, io)?;
+
+        // a file that's backed up isn't overwritten unless its backup can be made
+        let mut to_write = Vec::new();
+        for r in &rendered {
+            let backup = match resolve_conflict(r) {
+                Ok(true) if r.status == Status::Overwrite && r.policy == ConflictPolicy::Backup => {
+                    Backup::of(&r.path).map(Some)
+                }
+                Ok(true) => Ok(None),
+                Ok(false) => continue,
+                Err(e) => Err(e),
+            };
+
+            match backup {
+                Ok(backup) => to_write.push((r, backup)),
+                Err(e) => errors.push((r.path.display().to_string(), e)),
             }
+        }
 
-            write(&t.path, render)?;
-            writeln!(io.stdout(), "{}", path_prefix.join(name).display())
+        // recorded before anything is written, so the originals of modified files can be saved.
+        // Backups are recorded too, so undo removes them.
+        let mut manifest = Manifest::new(&spec_name.to_string_lossy());
+        for (r, backup) in &to_write {
+            let recorded = backup
+                .iter()
+                .try_for_each(|b| manifest.record(Path::new("."), &b.path, &b.contents))
+                .and_then(|_| match r.status {
+                    Status::Unchanged => Ok(()),
+                    _ => manifest.record(Path::new("."), &r.path, &r.contents),
+                });
+
+            if let Err(e) = recorded {
+                errors.push((r.path.display().to_string(), e));
+            }
+        }
+
+        let mut written = Vec::new();
+        let mut backed_up = Vec::new();
+        if !args.atomic {
+            for (r, backup) in to_write {
+                if let Some(b) = backup {
+                    if let Err(e) = b.write() {
+                        errors.push((r.path.display().to_string(), e));
+                        continue;
+                    }
+                    backed_up.push(b);
+                }
+
+                match r.write() {
+                    Ok(()) => written.push(r),
+                    Err(e) => errors.push((r.path.display().to_string(), e)),
+                }
+            }
+        } else if errors.is_empty() {
+            let (rendered, backups): (Vec<_>, Vec<_>) = to_write.into_iter().unzip();
+            let backups: Vec<_> = backups.into_iter().flatten().collect();
+
+            // backups are staged with the files, so a rolled back run doesn't leave any behind
+            match write_atomically(&rendered, &backups) {
+                Ok(()) => {
+                    written = rendered;
+                    backed_up = backups;
+                }
+                Err(e) => errors.push(("all templates".into(), e)),
+            }
+
+            for b in &backed_up {
+                if let Err(e) = b.set_permissions() {
+                    errors.push((b.path.display().to_string(), e));
+                }
+            }
+
+            for r in &written {
+                if let Err(e) = r.set_permissions() {
+                    errors.push((r.path.display().to_string(), e));
+                }
+            }
+        }
+
+        manifest.files.retain(|e| {
+            written.iter().any(|r| r.path == e.path) || backed_up.iter().any(|b| b.path == e.path)
+        });
+
+        if !manifest.files.is_empty()
+            && let Err(e) = manifest.save(Path::new("."))
+        {
+            errors.push(("manifest".into(), e));
+        }
+
+        for r in written {
+            writeln!(io.stdout(), "{}", path_prefix.join(&r.path).display())
                 .context("Failed to write name of path to stdout writer")?;
-            Ok(())
-        })();
+        }
+
+        // hooks run in order, and stop at the first one that fails, since later hooks usually
+        // depend on earlier ones
+        if run_hooks && errors.is_empty() {
+            for command in &post_hooks {
+                let display = hooks::display(command);
+                writeln!(io.stderr(), "Running {}", display)
+                    .context("Failed to write hook name to stderr writer")?;
 
-        if let Err(e) = result {
-            errors.push((t.path.display().to_string(), e));
+                if let Err(e) = hooks::run(command) {
+                    errors.push((format!("hook {}", display), e));
+                    break;
+                }
+            }
+        } else if run_hooks {
+            writeln!(
+                io.stderr(),
+                "Skipping hooks, not every template was generated"
+            )
+            .context("Failed to write hook summary to stderr writer")?;
         }
     }
 
@@ -144,6 +1043,17 @@ pub fn generate<Stdout: Write, Stderr: Write>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::schema::{Variable, VariableType};
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("0755").unwrap(), 0o755);
+        assert_eq!(parse_mode("644").unwrap(), 0o644);
+        assert_eq!(parse_mode("0o700").unwrap(), 0o700);
+        assert!(parse_mode("0999").is_err());
+        assert!(parse_mode("rwxr-xr-x").is_err());
+        assert!(parse_mode("17777").is_err());
+    }
 
     #[test]
     fn test_merge_options_empty_options() {
@@ -151,7 +1061,7 @@ mod tests {
         defaults.insert("user".to_string(), toml::Value::String("alice".to_string()));
         defaults.insert("count".to_string(), toml::Value::Integer(5));
 
-        let result = merge_options(&defaults, vec![]);
+        let result = merge_options(&defaults, &Schema::new(), vec![]).unwrap();
 
         assert_eq!(result, defaults);
     }
@@ -161,7 +1071,7 @@ mod tests {
         let defaults = toml::Table::new();
         let options = vec!["name".to_string(), "Bob".to_string()];
 
-        let result = merge_options(&defaults, options);
+        let result = merge_options(&defaults, &Schema::new(), options).unwrap();
 
         assert_eq!(
             result.get("name"),
@@ -174,7 +1084,7 @@ mod tests {
         let defaults = toml::Table::new();
         let options = vec!["count".to_string(), "42".to_string()];
 
-        let result = merge_options(&defaults, options);
+        let result = merge_options(&defaults, &Schema::new(), options).unwrap();
 
         assert_eq!(result.get("count"), Some(&toml::Value::Integer(42)));
     }
@@ -189,7 +1099,7 @@ mod tests {
             "false".to_string(),
         ];
 
-        let result = merge_options(&defaults, options);
+        let result = merge_options(&defaults, &Schema::new(), options).unwrap();
 
         assert_eq!(result.get("enabled"), Some(&toml::Value::Boolean(true)));
         assert_eq!(result.get("disabled"), Some(&toml::Value::Boolean(false)));
@@ -203,7 +1113,7 @@ mod tests {
 
         let options = vec!["user".to_string(), "bob".to_string()];
 
-        let result = merge_options(&defaults, options);
+        let result = merge_options(&defaults, &Schema::new(), options).unwrap();
 
         assert_eq!(
             result.get("user"),
@@ -227,7 +1137,7 @@ mod tests {
             "incomplete".to_string(),
         ];
 
-        let result = merge_options(&defaults, options);
+        let result = merge_options(&defaults, &Schema::new(), options).unwrap();
 
         assert_eq!(
             result.get("key1"),
@@ -240,16 +1150,152 @@ mod tests {
         ); // unchanged
     }
 
+    #[test]
+    fn test_merge_options_nested_keys() {
+        let mut defaults = toml::Table::new();
+        defaults.insert(
+            "db".to_string(),
+            toml::Value::Table(toml::toml! { host = "localhost"
+            port = 5432 }),
+        );
+
+        let options = vec!["db.port".to_string(), "6543".to_string()];
+
+        let result = merge_options(&defaults, &Schema::new(), options).unwrap();
+
+        assert_eq!(result["db"]["port"], toml::Value::Integer(6543));
+        assert_eq!(
+            result["db"]["host"],
+            toml::Value::String("localhost".to_string())
+        ); // unchanged
+    }
+
+    #[test]
+    fn test_merge_options_structured_values() {
+        let defaults = toml::Table::new();
+        let options = vec![
+            "features".to_string(),
+            r#"["a", "b"]"#.to_string(),
+            "deps".to_string(),
+            r#"{serde = "1"}"#.to_string(),
+        ];
+
+        let result = merge_options(&defaults, &Schema::new(), options).unwrap();
+
+        assert_eq!(
+            result.get("features"),
+            Some(&toml::Value::Array(vec!["a".into(), "b".into()]))
+        );
+        assert_eq!(
+            result["deps"]["serde"],
+            toml::Value::String("1".to_string())
+        );
+    }
+
     #[test]
     fn test_merge_options_invalid_toml_becomes_string() {
         let defaults = toml::Table::new();
         let options = vec!["weird".to_string(), "not-valid-toml!@#".to_string()];
 
-        let result = merge_options(&defaults, options);
+        let result = merge_options(&defaults, &Schema::new(), options).unwrap();
 
         assert_eq!(
             result.get("weird"),
             Some(&toml::Value::String("not-valid-toml!@#".to_string()))
         );
     }
+
+    #[test]
+    fn test_merge_options_follow_default_types() {
+        let defaults: toml::Table = toml::from_str(
+            r#"
+            version = "1.0"
+            zip = "00501"
+            ratio = 0.5
+            [db]
+            port = 5432
+            "#,
+        )
+        .unwrap();
+
+        let options = vec![
+            "version".to_string(),
+            "1.10".to_string(),
+            "zip".to_string(),
+            "007".to_string(),
+            "ratio".to_string(),
+            "2".to_string(),
+            "db.port".to_string(),
+            "6543".to_string(),
+            "new".to_string(),
+            "1.10".to_string(),
+        ];
+
+        let result = merge_options(&defaults, &Schema::new(), options).unwrap();
+
+        assert_eq!(result["version"], toml::Value::String("1.10".to_string()));
+        assert_eq!(result["zip"], toml::Value::String("007".to_string()));
+        assert_eq!(result["ratio"], toml::Value::Float(2.0));
+        assert_eq!(result["db"]["port"], toml::Value::Integer(6543));
+        assert_eq!(result["new"], toml::Value::Float(1.1)); // nothing to go by
+    }
+
+    #[test]
+    fn test_merge_options_follow_schema_types() {
+        let mut defaults = toml::Table::new();
+        defaults.insert("count".to_string(), toml::Value::Integer(1));
+
+        let mut schema = Schema::new();
+        for name in ["version", "count"] {
+            let definition = Variable {
+                kind: Some(VariableType::String),
+                ..Default::default()
+            };
+            schema.insert(name.to_string(), definition);
+        }
+
+        let options = vec![
+            "version".to_string(),
+            "1.10".to_string(),
+            "count".to_string(),
+            "2".to_string(),
+        ];
+
+        let result = merge_options(&defaults, &schema, options).unwrap();
+
+        // the schema's type is used when there's no default, the default's type comes first
+        assert_eq!(result["version"], toml::Value::String("1.10".to_string()));
+        assert_eq!(result["count"], toml::Value::Integer(2));
+    }
+
+    #[test]
+    fn test_merge_options_type_annotations() {
+        let mut defaults = toml::Table::new();
+        defaults.insert("count".to_string(), toml::Value::Integer(1));
+
+        let options = vec![
+            "version:str".to_string(),
+            "1.10".to_string(),
+            "count:str".to_string(),
+            "3".to_string(),
+            "db.port:int".to_string(),
+            "5432".to_string(),
+        ];
+
+        let result = merge_options(&defaults, &Schema::new(), options).unwrap();
+
+        assert_eq!(result["version"], toml::Value::String("1.10".to_string()));
+        assert_eq!(result["count"], toml::Value::String("3".to_string()));
+        assert_eq!(result["db"]["port"], toml::Value::Integer(5432));
+        assert!(!result.contains_key("version:str"));
+
+        let invalid = vec!["count:int".to_string(), "many".to_string()];
+        assert!(merge_options(&defaults, &Schema::new(), invalid).is_err());
+
+        // a suffix that isn't a type is part of the key
+        let colons = vec!["a:b".to_string(), "1".to_string()];
+        let result = merge_options(&defaults, &Schema::new(), colons).unwrap();
+        assert_eq!(result["a:b"], toml::Value::Integer(1));
+        assert!(!result.contains_key("a"));
+    }
 }
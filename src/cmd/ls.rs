@@ -43,7 +43,7 @@ fn list_with_vars<Stdout: Write, Stderr: Write>(
         let spec: Spec = specs.read_spec(&name)?;
 
         let vars = spec
-            .variables
+            .defaults()
             .iter()
             .map(|(k, v)| format!("{}={}", k, v))
             .collect::<Vec<_>>()
@@ -78,8 +78,8 @@ pub fn list<Stdout: Write, Stderr: Write>(
         return Ok(());
     }
 
-    // These functions assume everything in the entries vector is a regular file it can read. No
-    // directories.
+    // These functions assume everything in the entries vector is a spec they can read, either a
+    // regular file or a directory spec.
     if args.list_vars {
         list_with_vars(specs, all_specs, io)
     } else {
@@ -5,6 +5,7 @@ pub mod generate;
 pub mod ls;
 pub mod new;
 pub mod rm;
+pub mod undo;
 
 pub use cp::cp;
 pub use edit::edit;
@@ -12,3 +13,4 @@ pub use generate::generate;
 pub use ls::list;
 pub use new::new;
 pub use rm::rm;
+pub use undo::undo;
@@ -13,6 +13,7 @@ pub fn default_spec() -> Spec {
     let mut spec = Spec {
         variables: toml::Table::new(),
         templates: Vec::new(),
+        ..Default::default()
     };
 
     // this isn't super important, it's just for the sake of example
@@ -34,6 +35,7 @@ pub fn default_spec() -> Spec {
 Created by {{ user }}.
 "
         .into(),
+        ..Default::default()
     });
 
     spec
@@ -0,0 +1,53 @@
+use anyhow::{Context, Result, bail};
+use std::{io::Write, path::Path};
+
+use crate::cli::UndoArgs;
+use crate::{
+    io::IO,
+    manifest::{Manifest, Outcome},
+};
+
+/// undo subcommand entrypoint. It reverses the last gen in the working directory, using the
+/// manifest gen left behind.
+pub fn undo<Stdout: Write, Stderr: Write>(
+    args: UndoArgs,
+    io: &mut IO<Stdout, Stderr>,
+) -> Result<()> {
+    let root = args.workdir.as_deref().unwrap_or(Path::new(""));
+
+    let manifest = Manifest::load(root)?;
+    let spec = manifest.spec.clone();
+    let outcomes = manifest
+        .undo(root)
+        .context(format!("Unable to undo {}", spec))?;
+
+    let mut changed = 0;
+    for outcome in outcomes {
+        match outcome {
+            Outcome::Removed(path) => {
+                writeln!(io.stdout(), "Removed {}", root.join(path).display())?
+            }
+            Outcome::Restored(path) => {
+                writeln!(io.stdout(), "Restored {}", root.join(path).display())?
+            }
+            Outcome::Changed(path) => {
+                changed += 1;
+                writeln!(
+                    io.stderr(),
+                    "Skipping {}, it changed since it was generated",
+                    root.join(path).display()
+                )?;
+            }
+        }
+    }
+
+    if changed > 0 {
+        bail!(
+            "{} file(s) changed since {} was generated, and were left alone",
+            changed,
+            spec
+        );
+    }
+
+    Ok(())
+}
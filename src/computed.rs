@@ -0,0 +1,204 @@
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::{Context, Result, bail, ensure};
+use minijinja::{Environment, Value};
+
+use crate::vars;
+
+/// Evaluate computed variables, adding them to variables. Each one is a minijinja expression, like
+/// name | snake_case, or a template, like {{ name | snake_case }}-cli, evaluated against the other
+/// variables. A template always results in a string. Computed variables can refer to each other,
+/// and are evaluated after the ones they refer to. Variables in skip, like the ones given on the
+/// command line, aren't computed.
+pub fn evaluate(
+    env: &Environment,
+    computed: &BTreeMap<String, String>,
+    variables: &mut toml::Table,
+    skip: &[String],
+) -> Result<()> {
+    let mut order = Vec::new();
+    for name in computed.keys() {
+        visit(env, computed, name, &mut Vec::new(), &mut order)?;
+    }
+
+    for name in order {
+        if skip.contains(name) {
+            continue;
+        }
+
+        let expression = &computed[name];
+        let value = eval(env, expression, vars::context(variables)).context(format!(
+            "Unable to evaluate computed variable {} = \"{}\"",
+            name, expression
+        ))?;
+
+        ensure!(
+            !value.is_undefined() && !value.is_none(),
+            "Computed variable {} = \"{}\" doesn't have a value",
+            name,
+            expression
+        );
+
+        let value = toml::Value::try_from(&value).context(format!(
+            "Computed variable {} = \"{}\" isn't a valid TOML value",
+            name, expression
+        ))?;
+
+        variables.insert(name.clone(), value);
+    }
+
+    Ok(())
+}
+
+/// Add a computed variable to order, after every computed variable it refers to. path holds the
+/// variables that are being visited, to detect variables that end up referring to themselves.
+fn visit<'a>(
+    env: &Environment,
+    computed: &'a BTreeMap<String, String>,
+    name: &'a String,
+    path: &mut Vec<&'a str>,
+    order: &mut Vec<&'a String>,
+) -> Result<()> {
+    if order.contains(&name) {
+        return Ok(());
+    }
+
+    if let Some(start) = path.iter().position(|n| *n == name) {
+        let mut cycle = path[start..].to_vec();
+        cycle.push(name);
+        bail!(
+            "Computed variables refer to each other: {}",
+            cycle.join(" -> ")
+        );
+    }
+
+    let expression = &computed[name];
+    let dependencies = undeclared_variables(env, expression).context(format!(
+        "Unable to parse computed variable {} = \"{}\"",
+        name, expression
+    ))?;
+
+    let mut dependencies: Vec<_> = dependencies.into_iter().collect();
+    dependencies.sort();
+
+    path.push(name);
+    for dependency in dependencies {
+        if let Some((dependency, _)) = computed.get_key_value(&dependency) {
+            visit(env, computed, dependency, path, order)?;
+        }
+    }
+    path.pop();
+
+    order.push(name);
+    Ok(())
+}
+
+/// Whether a computed variable is a template rather than an expression.
+fn is_template(source: &str) -> bool {
+    source.contains("{{") || source.contains("{%")
+}
+
+/// Evaluate a computed variable's expression, or render its template.
+fn eval(env: &Environment, source: &str, context: Value) -> Result<Value, minijinja::Error> {
+    if is_template(source) {
+        env.render_str(source, context).map(Value::from)
+    } else {
+        env.compile_expression(source)?.eval(context)
+    }
+}
+
+/// The variables a computed variable refers to.
+fn undeclared_variables(env: &Environment, source: &str) -> Result<HashSet<String>> {
+    if is_template(source) {
+        Ok(env.template_from_str(source)?.undeclared_variables(false))
+    } else {
+        Ok(env.compile_expression(source)?.undeclared_variables(false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn computed(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn evaluated_in_dependency_order() {
+        let computed = computed(&[
+            ("a_path", "'src/' ~ slug ~ '.rs'"),
+            ("slug", "name | lower | replace(' ', '-')"),
+            ("count", "items | length"),
+        ]);
+
+        let mut variables = toml::Table::new();
+        variables.insert("name".into(), "My Crate".into());
+        variables.insert(
+            "items".into(),
+            toml::Value::Array(vec!["x".into(), "y".into()]),
+        );
+
+        evaluate(&Environment::new(), &computed, &mut variables, &[]).unwrap();
+
+        assert_eq!(variables["slug"].as_str(), Some("my-crate"));
+        assert_eq!(variables["a_path"].as_str(), Some("src/my-crate.rs"));
+        assert_eq!(variables["count"].as_integer(), Some(2));
+    }
+
+    #[test]
+    fn templates_are_rendered() {
+        let computed = computed(&[
+            ("crate_ident", "{{ project | replace('-', '_') }}"),
+            ("binary", "{{ crate_ident }}-cli"),
+            ("version", "{% if stable %}1.0{% else %}0.1{% endif %}"),
+        ]);
+
+        let mut variables = toml::Table::new();
+        variables.insert("project".into(), "my-crate".into());
+        variables.insert("stable".into(), true.into());
+
+        evaluate(&Environment::new(), &computed, &mut variables, &[]).unwrap();
+
+        assert_eq!(variables["crate_ident"].as_str(), Some("my_crate"));
+        assert_eq!(variables["binary"].as_str(), Some("my_crate-cli"));
+        assert_eq!(variables["version"].as_str(), Some("1.0"));
+    }
+
+    #[test]
+    fn skipped_variables_keep_their_value() {
+        let computed = computed(&[("slug", "name | lower")]);
+
+        let mut variables = toml::Table::new();
+        variables.insert("name".into(), "Crate".into());
+        variables.insert("slug".into(), "given".into());
+
+        let skip = vec!["slug".to_string()];
+        evaluate(&Environment::new(), &computed, &mut variables, &skip).unwrap();
+
+        assert_eq!(variables["slug"].as_str(), Some("given"));
+    }
+
+    #[test]
+    fn cycles_are_errors() {
+        let computed = computed(&[("a", "b ~ 'x'"), ("b", "c ~ 'y'"), ("c", "a ~ 'z'")]);
+
+        let mut variables = toml::Table::new();
+        let err = evaluate(&Environment::new(), &computed, &mut variables, &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Computed variables refer to each other: a -> b -> c -> a"
+        );
+    }
+
+    #[test]
+    fn undefined_results_are_errors() {
+        let computed = computed(&[("missing", "nothing_here")]);
+
+        let mut variables = toml::Table::new();
+        assert!(evaluate(&Environment::new(), &computed, &mut variables, &[]).is_err());
+    }
+}
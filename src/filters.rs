@@ -0,0 +1,187 @@
+use std::fmt::{Display, Write};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use heck::{
+    ToKebabCase, ToLowerCamelCase, ToShoutySnakeCase, ToSnakeCase, ToTitleCase, ToUpperCamelCase,
+};
+use minijinja::{Environment, Error, ErrorKind, Value};
+
+use crate::inflect;
+
+/// Register tmpl's filters on an environment, on top of minijinja's built in ones. Most of them
+/// convert between the naming conventions a project needs, e.g. {{ name | pascal_case }}.
+pub fn register(env: &mut Environment) {
+    env.add_filter("snake_case", snake_case);
+    env.add_filter("camel_case", camel_case);
+    env.add_filter("pascal_case", pascal_case);
+    env.add_filter("kebab_case", kebab_case);
+    env.add_filter("shouty_snake_case", shouty_snake_case);
+    env.add_filter("title_case", title_case);
+    env.add_filter("pluralize", pluralize);
+    env.add_filter("singularize", singularize);
+    env.add_filter("date", date);
+}
+
+fn snake_case(value: &str) -> String {
+    value.to_snake_case()
+}
+
+fn camel_case(value: &str) -> String {
+    value.to_lower_camel_case()
+}
+
+fn pascal_case(value: &str) -> String {
+    value.to_upper_camel_case()
+}
+
+fn kebab_case(value: &str) -> String {
+    value.to_kebab_case()
+}
+
+fn shouty_snake_case(value: &str) -> String {
+    value.to_shouty_snake_case()
+}
+
+fn title_case(value: &str) -> String {
+    value.to_title_case()
+}
+
+fn pluralize(value: &str) -> String {
+    inflect::pluralize(value)
+}
+
+fn singularize(value: &str) -> String {
+    inflect::singularize(value)
+}
+
+/// Format a date with a strftime style format, e.g. {{ now() | date("%B %-d, %Y") }}. The value can
+/// be an RFC 3339 datetime, a datetime without an offset, a date, or a TOML datetime variable,
+/// which is a string by the time it's in a template's context. The format defaults to %Y-%m-%d.
+fn date(value: Value, format: Option<&str>) -> Result<String, Error> {
+    let format = format.unwrap_or("%Y-%m-%d");
+
+    let Some(text) = value.as_str() else {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("{} is not a date", value),
+        ));
+    };
+
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(text) {
+        return strftime(datetime.format(format));
+    }
+
+    for datetime_format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(text, datetime_format) {
+            return strftime(datetime.format(format));
+        }
+    }
+
+    match NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        Ok(date) => strftime(date.format(format)),
+        Err(_) => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("{} is not a date", text),
+        )),
+    }
+}
+
+/// Write a formatted date to a string. An invalid format is only noticed when it's written.
+fn strftime(formatted: impl Display) -> Result<String, Error> {
+    let mut out = String::new();
+    write!(out, "{}", formatted)
+        .map_err(|_| Error::new(ErrorKind::InvalidOperation, "invalid date format"))?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use minijinja::context;
+
+    use super::*;
+
+    fn render(template: &str, name: &str) -> String {
+        let mut env = Environment::new();
+        register(&mut env);
+        env.render_str(template, context! { name }).unwrap()
+    }
+
+    #[test]
+    fn case_conversions() {
+        let name = "user account";
+        assert_eq!(render("{{ name | snake_case }}", name), "user_account");
+        assert_eq!(render("{{ name | camel_case }}", name), "userAccount");
+        assert_eq!(render("{{ name | pascal_case }}", name), "UserAccount");
+        assert_eq!(render("{{ name | kebab_case }}", name), "user-account");
+        assert_eq!(
+            render("{{ name | shouty_snake_case }}", name),
+            "USER_ACCOUNT"
+        );
+        assert_eq!(render("{{ name | title_case }}", name), "User Account");
+
+        // conversions work from any convention, not just words
+        assert_eq!(
+            render("{{ name | snake_case }}", "UserAccount"),
+            "user_account"
+        );
+        assert_eq!(
+            render("{{ name | pascal_case }}", "user-account"),
+            "UserAccount"
+        );
+        assert_eq!(
+            render("{{ name | kebab_case }}", "userAccount"),
+            "user-account"
+        );
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(
+            render("{{ name | date }}", "1979-05-27T07:32:00Z"),
+            "1979-05-27"
+        );
+        assert_eq!(
+            render("{{ name | date(\"%H:%M\") }}", "1979-05-27 07:32:00"),
+            "07:32"
+        );
+        assert_eq!(
+            render("{{ name | date(\"%B %-d, %Y\") }}", "1979-05-27"),
+            "May 27, 1979"
+        );
+
+        // TOML datetimes are strings in the context
+        let mut variables = toml::Table::new();
+        variables.insert(
+            "released".into(),
+            toml::Value::Datetime("1979-05-27T07:32:00-08:00".parse().unwrap()),
+        );
+
+        let mut env = Environment::new();
+        register(&mut env);
+        let context = crate::vars::context(&variables);
+        let year = env.render_str("{{ released | date(\"%Y\") }}", context);
+        assert_eq!(year.unwrap(), "1979");
+    }
+
+    #[test]
+    fn invalid_dates_are_errors() {
+        let mut env = Environment::new();
+        register(&mut env);
+
+        let not_a_date = env.render_str("{{ 'yesterday' | date }}", context! {});
+        assert!(not_a_date.is_err());
+
+        let bad_format = env.render_str("{{ '1979-05-27' | date('%Q') }}", context! {});
+        assert!(bad_format.is_err());
+    }
+
+    #[test]
+    fn plurals() {
+        assert_eq!(render("{{ name | pluralize }}", "category"), "categories");
+        assert_eq!(render("{{ name | singularize }}", "children"), "child");
+        assert_eq!(
+            render("{{ name | singularize | pascal_case }}", "order_items"),
+            "OrderItem"
+        );
+    }
+}
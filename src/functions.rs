@@ -0,0 +1,117 @@
+use std::env;
+
+use chrono::{Local, SecondsFormat};
+use minijinja::{Environment, Error, ErrorKind};
+use uuid::Uuid;
+
+/// Register tmpl's global functions on an environment, for values that don't come from the spec,
+/// like the current date, e.g. Copyright {{ now() | date("%Y") }}.
+pub fn register(env: &mut Environment) {
+    env.add_function("now", now);
+    env.add_function("today", today);
+    env.add_function("uuid4", uuid4);
+    env.add_function("env", env_var);
+    env.add_function("cwd_name", cwd_name);
+    env.add_function("hostname", hostname);
+}
+
+/// The current local date and time, in RFC 3339 format.
+fn now() -> String {
+    Local::now().to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+/// The current local date, as YYYY-MM-DD.
+fn today() -> String {
+    Local::now().date_naive().to_string()
+}
+
+/// A random UUID.
+fn uuid4() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// The value of an environment variable. It's an error if the variable isn't set, unless there's
+/// a default.
+fn env_var(name: &str, default: Option<String>) -> Result<String, Error> {
+    match (env::var(name), default) {
+        (Ok(value), _) => Ok(value),
+        (Err(_), Some(default)) => Ok(default),
+        (Err(e), None) => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!(
+                "environment variable {} isn't usable and has no default",
+                name
+            ),
+        )
+        .with_source(e)),
+    }
+}
+
+/// The name of the directory the spec is being generated in.
+fn cwd_name() -> Result<String, Error> {
+    let cwd = env::current_dir().map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            "unable to get the current directory",
+        )
+        .with_source(e)
+    })?;
+
+    Ok(cwd
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default())
+}
+
+/// The machine's hostname.
+fn hostname() -> Result<String, Error> {
+    hostname::get()
+        .map(|name| name.to_string_lossy().into_owned())
+        .map_err(|e| {
+            Error::new(ErrorKind::InvalidOperation, "unable to get the hostname").with_source(e)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use minijinja::context;
+
+    use super::*;
+
+    fn render(template: &str) -> Result<String, Error> {
+        let mut env = Environment::new();
+        register(&mut env);
+        env.render_str(template, context! {})
+    }
+
+    #[test]
+    fn dates() {
+        let today = render("{{ today() }}").unwrap();
+        assert_eq!(today.len(), "2025-01-31".len());
+        assert!(render("{{ now() }}").unwrap().starts_with(&today));
+    }
+
+    #[test]
+    fn uuids_are_random() {
+        let uuid = render("{{ uuid4() }}").unwrap();
+        assert!(Uuid::parse_str(&uuid).is_ok());
+        assert_ne!(uuid, render("{{ uuid4() }}").unwrap());
+    }
+
+    #[test]
+    fn env_falls_back_to_the_default() {
+        let unset = "TMPL_TEST_SURELY_UNSET_VARIABLE";
+        let with_default = format!("{{{{ env(\"{}\", \"fallback\") }}}}", unset);
+        assert_eq!(render(&with_default).unwrap(), "fallback");
+
+        let without_default = format!("{{{{ env(\"{}\") }}}}", unset);
+        assert!(render(&without_default).is_err());
+    }
+
+    #[test]
+    fn cwd_name_is_the_directory_name() {
+        let expected = env::current_dir().unwrap();
+        let expected = expected.file_name().unwrap().to_string_lossy();
+        assert_eq!(render("{{ cwd_name() }}").unwrap(), expected);
+    }
+}
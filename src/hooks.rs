@@ -0,0 +1,67 @@
+use std::process::Command;
+
+use anyhow::{Context, Result, ensure};
+use minijinja::{Environment, Value};
+
+use crate::specs::Hook;
+
+/// Render each of a hook's arguments with the context, producing the command to run.
+pub fn render(env: &Environment, hook: &Hook, context: &Value) -> Result<Vec<String>> {
+    ensure!(!hook.run.is_empty(), "A hook needs a command to run");
+
+    hook.run
+        .iter()
+        .map(|arg| {
+            env.render_str(arg, context)
+                .context(format!("Unable to render {}", arg))
+        })
+        .collect()
+}
+
+/// Run a rendered command in the current directory. The command shares tmpl's stdin, stdout and
+/// stderr, and it's an error if it doesn't exit successfully.
+pub fn run(command: &[String]) -> Result<()> {
+    let (program, args) = command
+        .split_first()
+        .context("A hook needs a command to run")?;
+
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .context(format!("Unable to run {}", program))?;
+
+    ensure!(status.success(), "{} exited with {}", program, status);
+    Ok(())
+}
+
+/// How a command is shown to the user.
+pub fn display(command: &[String]) -> String {
+    command.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arguments_are_rendered() {
+        let hook = Hook {
+            run: vec!["cargo".into(), "new".into(), "{{ name }}".into()],
+            ..Default::default()
+        };
+        let context = Value::from_iter([("name", "demo")]);
+
+        let command = render(&Environment::new(), &hook, &context).unwrap();
+        assert_eq!(display(&command), "cargo new demo");
+
+        assert!(render(&Environment::new(), &Hook::default(), &context).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_commands_are_errors() {
+        assert!(run(&["true".into()]).is_ok());
+        assert!(run(&["false".into()]).is_err());
+        assert!(run(&["tmpl-no-such-program".into()]).is_err());
+    }
+}
@@ -0,0 +1,215 @@
+/// Words that are the same in the singular and the plural.
+const UNCOUNTABLE: &[&str] = &[
+    "data",
+    "deer",
+    "equipment",
+    "feedback",
+    "fish",
+    "hardware",
+    "information",
+    "media",
+    "metadata",
+    "money",
+    "news",
+    "rice",
+    "series",
+    "sheep",
+    "software",
+    "species",
+];
+
+/// Singular and plural pairs the suffix rules get wrong.
+const IRREGULAR: &[(&str, &str)] = &[
+    ("alias", "aliases"),
+    ("bonus", "bonuses"),
+    ("bus", "buses"),
+    ("campus", "campuses"),
+    ("canvas", "canvases"),
+    ("child", "children"),
+    ("cookie", "cookies"),
+    ("criterion", "criteria"),
+    ("crisis", "crises"),
+    ("diagnosis", "diagnoses"),
+    ("echo", "echoes"),
+    ("foot", "feet"),
+    ("goose", "geese"),
+    ("half", "halves"),
+    ("hero", "heroes"),
+    ("knife", "knives"),
+    ("leaf", "leaves"),
+    ("life", "lives"),
+    ("man", "men"),
+    ("mouse", "mice"),
+    ("movie", "movies"),
+    ("ox", "oxen"),
+    ("person", "people"),
+    ("potato", "potatoes"),
+    ("quiz", "quizzes"),
+    ("shelf", "shelves"),
+    ("status", "statuses"),
+    ("thesis", "theses"),
+    ("thief", "thieves"),
+    ("tomato", "tomatoes"),
+    ("tooth", "teeth"),
+    ("virus", "viruses"),
+    ("wife", "wives"),
+    ("wolf", "wolves"),
+    ("woman", "women"),
+    ("zombie", "zombies"),
+];
+
+/// The plural form of an English noun. Only the last word of a name is changed, so user_account,
+/// UserAccount and user-account become user_accounts, UserAccounts and user-accounts.
+pub fn pluralize(name: &str) -> String {
+    inflect(name, |word| {
+        if let Some((_, plural)) = IRREGULAR.iter().find(|(singular, _)| *singular == word) {
+            return Change::Replace(plural);
+        }
+
+        let consonant_y = word.ends_with('y')
+            && !["ay", "ey", "iy", "oy", "uy"]
+                .iter()
+                .any(|suffix| word.ends_with(suffix));
+
+        if word.ends_with("sis") {
+            Change::Suffix(2, "es")
+        } else if word.ends_with(['s', 'x', 'z']) || word.ends_with("ch") || word.ends_with("sh") {
+            Change::Suffix(0, "es")
+        } else if consonant_y {
+            Change::Suffix(1, "ies")
+        } else {
+            Change::Suffix(0, "s")
+        }
+    })
+}
+
+/// The singular form of an English noun. Like pluralize, only the last word of a name is changed.
+pub fn singularize(name: &str) -> String {
+    inflect(name, |word| {
+        if let Some((singular, _)) = IRREGULAR.iter().find(|(_, plural)| *plural == word) {
+            return Change::Replace(singular);
+        }
+
+        if word.ends_with("yses") {
+            Change::Suffix(2, "is")
+        } else if word.len() > 4 && word.ends_with("ies") {
+            Change::Suffix(3, "y")
+        } else if ["sses", "xes", "zzes", "ches", "shes"]
+            .iter()
+            .any(|suffix| word.ends_with(suffix))
+        {
+            Change::Suffix(2, "")
+        } else if word.ends_with('s') && !word.ends_with("ss") && !word.ends_with("us") {
+            Change::Suffix(1, "")
+        } else {
+            Change::Suffix(0, "")
+        }
+    })
+}
+
+/// Change describes how to inflect a word, given in lowercase.
+enum Change {
+    /// Replace the whole word.
+    Replace(&'static str),
+
+    /// Remove some number of characters from the end of the word, then append a suffix.
+    Suffix(usize, &'static str),
+}
+
+/// Apply a change to the last word of a name, keeping the word's case.
+fn inflect(name: &str, change: impl Fn(&str) -> Change) -> String {
+    let (head, word) = name.split_at(last_word(name));
+    let lower = word.to_lowercase();
+
+    if word.is_empty() || UNCOUNTABLE.contains(&lower.as_str()) {
+        return name.to_string();
+    }
+
+    let shouting = word.chars().count() > 1 && !word.chars().any(char::is_lowercase);
+
+    let inflected = match change(&lower) {
+        Change::Replace(replacement) if shouting => replacement.to_uppercase(),
+        Change::Replace(replacement) => match word.chars().next() {
+            Some(first) if first.is_uppercase() => capitalize(replacement),
+            _ => replacement.to_string(),
+        },
+        Change::Suffix(remove, suffix) => {
+            let suffix = if shouting {
+                suffix.to_uppercase()
+            } else {
+                suffix.to_string()
+            };
+            let keep = word.chars().count().saturating_sub(remove);
+            let stem: String = word.chars().take(keep).collect();
+            format!("{}{}", stem, suffix)
+        }
+    };
+
+    format!("{}{}", head, inflected)
+}
+
+/// The byte offset the last word of a name starts at. Words are separated by spaces, underscores
+/// and dashes, or start with an uppercase letter that follows a lowercase one.
+fn last_word(name: &str) -> usize {
+    let mut start = 0;
+    let mut previous: Option<char> = None;
+
+    for (i, c) in name.char_indices() {
+        if matches!(c, ' ' | '_' | '-') {
+            start = i + c.len_utf8();
+        } else if c.is_uppercase() && previous.is_some_and(|p| p.is_lowercase() || p.is_numeric()) {
+            start = i;
+        }
+        previous = Some(c);
+    }
+
+    start
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORDS: &[(&str, &str)] = &[
+        ("user", "users"),
+        ("box", "boxes"),
+        ("match", "matches"),
+        ("class", "classes"),
+        ("city", "cities"),
+        ("day", "days"),
+        ("analysis", "analyses"),
+        ("house", "houses"),
+        ("person", "people"),
+        ("mouse", "mice"),
+        ("leaf", "leaves"),
+        ("hero", "heroes"),
+        ("status", "statuses"),
+        ("sheep", "sheep"),
+    ];
+
+    #[test]
+    fn round_trips() {
+        for (singular, plural) in WORDS {
+            assert_eq!(pluralize(singular), *plural);
+            assert_eq!(singularize(plural), *singular);
+        }
+    }
+
+    #[test]
+    fn only_the_last_word_changes() {
+        assert_eq!(pluralize("user_account"), "user_accounts");
+        assert_eq!(pluralize("UserAccount"), "UserAccounts");
+        assert_eq!(pluralize("sales-person"), "sales-people");
+        assert_eq!(pluralize("USER_CATEGORY"), "USER_CATEGORIES");
+        assert_eq!(singularize("OrderItems"), "OrderItem");
+        assert_eq!(singularize("Children"), "Child");
+    }
+}
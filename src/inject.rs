@@ -0,0 +1,212 @@
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+
+use crate::specs::Inject;
+
+/// Insert a rendered body into the contents of an existing file. Injection is idempotent, if the
+/// body's lines are already in the file, as whole lines, it's returned unchanged. For before and
+/// after, only the lines next to the marker count: the body has to be right where it would be
+/// inserted, or among the lines next to the marker up to the nearest blank line, where bodies
+/// injected by earlier templates end up. Before and after insert the body on its own line(s), next
+/// to the first line matching their pattern.
+pub fn inject(existing: Option<&str>, body: &str, how: &Inject) -> Result<String> {
+    let existing = match (existing, how) {
+        (Some(existing), _) => existing,
+        (None, Inject::Append | Inject::Prepend) => return Ok(body.to_string()),
+        (None, _) => bail!("Nothing to inject into, the file doesn't exist"),
+    };
+
+    let lines: Vec<_> = existing.lines().collect();
+    let body_lines: Vec<_> = body.lines().collect();
+    let marker = match how {
+        Inject::Before(pattern) | Inject::After(pattern) => Some(find_line(existing, pattern)?),
+        Inject::Append | Inject::Prepend => None,
+    };
+
+    // bodies injected next to a marker pile up there, until a blank line
+    let region = match (how, marker) {
+        (Inject::Before(_), Some(marker)) => {
+            let above = &lines[..marker.index];
+            let start = above.iter().rposition(|l| l.trim().is_empty());
+            &above[start.map_or(0, |i| i + 1)..]
+        }
+        (Inject::After(_), Some(marker)) => {
+            let below = &lines[marker.index + 1..];
+            let end = below.iter().position(|l| l.trim().is_empty());
+            &below[..end.unwrap_or(below.len())]
+        }
+        _ => &lines[..],
+    };
+
+    // a body with blank lines in it can't be inside the region, but it's still found right where
+    // it would be inserted
+    let at_insertion_point = match (how, marker) {
+        (Inject::Before(_), Some(marker)) => lines[..marker.index].ends_with(&body_lines),
+        (Inject::After(_), Some(marker)) => lines[marker.index + 1..].starts_with(&body_lines),
+        _ => false,
+    };
+
+    let already_there = at_insertion_point
+        || region
+            .windows(body_lines.len().max(1))
+            .any(|window| window == body_lines);
+
+    if !body_lines.is_empty() && already_there {
+        return Ok(existing.to_string());
+    }
+
+    let body = with_newline(body);
+
+    let contents = match (how, marker) {
+        (Inject::Append, _) => format!("{}{}", with_newline(existing), body),
+        (Inject::Prepend, _) => format!("{}{}", body, existing),
+        (Inject::Before(_), Some(marker)) => {
+            let (head, tail) = existing.split_at(marker.start);
+            format!("{}{}{}", head, body, tail)
+        }
+        (Inject::After(_), Some(marker)) => {
+            let (head, tail) = existing.split_at(marker.end);
+            format!("{}{}{}", with_newline(head), body, tail)
+        }
+        _ => unreachable!("before and after always have a marker"),
+    };
+
+    Ok(contents)
+}
+
+/// A line matching an inject pattern.
+#[derive(Clone, Copy)]
+struct Marker {
+    /// The line's index among the file's lines.
+    index: usize,
+
+    /// The offsets of the line's start and end. The end includes the line's newline, if it has
+    /// one.
+    start: usize,
+    end: usize,
+}
+
+/// Find the first line matching the pattern.
+fn find_line(contents: &str, pattern: &str) -> Result<Marker> {
+    let re = Regex::new(pattern).context(format!("{} is not a valid pattern", pattern))?;
+
+    let mut start = 0;
+    for (index, line) in contents.split_inclusive('\n').enumerate() {
+        if re.is_match(line.trim_end_matches(['\r', '\n'])) {
+            return Ok(Marker {
+                index,
+                start,
+                end: start + line.len(),
+            });
+        }
+        start += line.len();
+    }
+
+    bail!("No line matches {}", pattern)
+}
+
+/// Make sure non-empty text ends with a newline, so injected text lands on its own line.
+fn with_newline(text: &str) -> String {
+    if text.is_empty() || text.ends_with('\n') {
+        text.to_string()
+    } else {
+        format!("{}\n", text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXISTING: &str = "mod a;\n// tmpl:mods\n\nfn main() {}\n";
+
+    #[test]
+    fn before() {
+        let how = Inject::Before("tmpl:mods".into());
+        let result = inject(Some(EXISTING), "mod b;", &how).unwrap();
+        assert_eq!(result, "mod a;\nmod b;\n// tmpl:mods\n\nfn main() {}\n");
+    }
+
+    #[test]
+    fn after() {
+        let how = Inject::After("^// tmpl:mods$".into());
+        let result = inject(Some(EXISTING), "mod b;\n", &how).unwrap();
+        assert_eq!(result, "mod a;\n// tmpl:mods\nmod b;\n\nfn main() {}\n");
+    }
+
+    #[test]
+    fn after_last_line_without_newline() {
+        let how = Inject::After("marker".into());
+        let result = inject(Some("marker"), "added", &how).unwrap();
+        assert_eq!(result, "marker\nadded\n");
+    }
+
+    #[test]
+    fn append_and_prepend() {
+        let result = inject(Some("one"), "two", &Inject::Append).unwrap();
+        assert_eq!(result, "one\ntwo\n");
+
+        let result = inject(Some("one\n"), "zero", &Inject::Prepend).unwrap();
+        assert_eq!(result, "zero\none\n");
+
+        let result = inject(None, "new", &Inject::Append).unwrap();
+        assert_eq!(result, "new");
+    }
+
+    #[test]
+    fn is_idempotent() {
+        let how = Inject::Before("tmpl:mods".into());
+        let once = inject(Some(EXISTING), "mod b;", &how).unwrap();
+        let twice = inject(Some(&once), "mod b;", &how).unwrap();
+        assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn only_the_insertion_point_counts() {
+        // mod b; is in the file, but not on its own line after the marker
+        let how = Inject::After("^mod a;".into());
+        let result = inject(Some("mod a;\nmod b;x\n"), "mod b;", &how).unwrap();
+        assert_eq!(result, "mod a;\nmod b;\nmod b;x\n");
+
+        // the same line in another section doesn't count either
+        let toml = "[dependencies]\n\n[dev-dependencies]\nserde = \"1\"\n";
+        let how = Inject::After(r"^\[dependencies\]".into());
+        let result = inject(Some(toml), "serde = \"1\"", &how).unwrap();
+        assert_eq!(
+            result,
+            "[dependencies]\nserde = \"1\"\n\n[dev-dependencies]\nserde = \"1\"\n"
+        );
+        assert_eq!(
+            inject(Some(&result), "serde = \"1\"", &how).unwrap(),
+            result
+        );
+
+        let result = inject(Some("one\ntwo three\n"), "two", &Inject::Append).unwrap();
+        assert_eq!(result, "one\ntwo three\ntwo\n");
+    }
+
+    #[test]
+    fn bodies_with_blank_lines_are_idempotent() {
+        let body = "fn a() {}\n\nfn b() {}\n";
+
+        for how in [
+            Inject::After("// routes".into()),
+            Inject::Before("// routes".into()),
+        ] {
+            let existing = "use x;\n// routes\n\nfn main() {}\n";
+            let once = inject(Some(existing), body, &how).unwrap();
+            let twice = inject(Some(&once), body, &how).unwrap();
+            let thrice = inject(Some(&twice), body, &how).unwrap();
+            assert_eq!(once, twice);
+            assert_eq!(once, thrice);
+            assert_eq!(once.matches("fn a() {}").count(), 1);
+        }
+    }
+
+    #[test]
+    fn missing_marker_or_file_is_an_error() {
+        let how = Inject::After("nope".into());
+        assert!(inject(Some(EXISTING), "mod b;", &how).is_err());
+        assert!(inject(None, "mod b;", &how).is_err());
+    }
+}
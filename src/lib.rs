@@ -1,8 +1,18 @@
+mod computed;
 mod editor;
+mod filters;
+mod functions;
+mod hooks;
+mod inflect;
+mod inject;
+mod manifest;
 mod path;
 mod prompt;
+mod transaction;
+mod vars;
 
 pub mod cli;
 pub mod cmd;
 pub mod io;
+pub mod schema;
 pub mod specs;
@@ -32,6 +32,7 @@ fn main() -> anyhow::Result<()> {
         cli::Commands::Edit(args) => cmd::edit(&specs, args)?,
         cli::Commands::Cp(args) => cmd::cp(&specs, args)?,
         cli::Commands::Rm(args) => cmd::rm(&specs, args, &mut io)?,
+        cli::Commands::Undo(args) => cmd::undo(args, &mut io)?,
     }
 
     Ok(())
@@ -0,0 +1,263 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// The directory in the output directory that tmpl keeps its state in.
+pub const STATE_DIR: &str = ".tmpl";
+
+const MANIFEST_FILE: &str = "manifest.toml";
+const ORIGINALS_DIR: &str = "originals";
+
+/// Manifest records the files a generation run created or modified, so that the run can be
+/// undone. Copies of modified files are kept next to the manifest, in the state directory.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Manifest {
+    pub spec: String,
+    pub files: Vec<Entry>,
+}
+
+/// Entry is a single file written during a run.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub action: Action,
+
+    /// The SHA-256 checksum of the file as tmpl wrote it.
+    pub checksum: String,
+
+    /// The name of the copy of the original file, in the originals directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original: Option<String>,
+
+    #[serde(skip)]
+    original_contents: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Created,
+    Modified,
+}
+
+/// Outcome is what undoing a single entry did.
+pub enum Outcome {
+    Removed(PathBuf),
+    Restored(PathBuf),
+    Changed(PathBuf),
+}
+
+/// Calculate the hex encoded SHA-256 checksum of some contents.
+pub fn checksum(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+impl Manifest {
+    pub fn new(spec: &str) -> Self {
+        Self {
+            spec: spec.to_string(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Record a file that's about to be written, relative to root. The first time a path is
+    /// recorded, its original contents are read so the file can be restored later.
+    pub fn record(&mut self, root: &Path, path: &Path, contents: &[u8]) -> Result<()> {
+        if let Some(entry) = self.files.iter_mut().find(|e| e.path == path) {
+            entry.checksum = checksum(contents);
+            return Ok(());
+        }
+
+        let target = root.join(path);
+        let (action, original_contents) = if target.exists() {
+            let original =
+                fs::read(&target).context(format!("Unable to read {}", path.display()))?;
+            (Action::Modified, Some(original))
+        } else {
+            (Action::Created, None)
+        };
+
+        self.files.push(Entry {
+            path: path.to_path_buf(),
+            action,
+            checksum: checksum(contents),
+            original: None,
+            original_contents,
+        });
+
+        Ok(())
+    }
+
+    /// Save the manifest and the originals of modified files to the state directory under root,
+    /// replacing whatever an earlier run left there.
+    pub fn save(&mut self, root: &Path) -> Result<()> {
+        let state = root.join(STATE_DIR);
+        if state.exists() {
+            fs::remove_dir_all(&state).context("Unable to clear the previous manifest")?;
+        }
+
+        let originals = state.join(ORIGINALS_DIR);
+        fs::create_dir_all(&originals).context("Unable to create the state directory")?;
+
+        for (i, entry) in self.files.iter_mut().enumerate() {
+            if let Some(contents) = &entry.original_contents {
+                let name = i.to_string();
+                fs::write(originals.join(&name), contents).context(format!(
+                    "Unable to save the original {}",
+                    entry.path.display()
+                ))?;
+                entry.original = Some(name);
+            }
+        }
+
+        let toml_string = toml::to_string(self).context("Failed to serialize the manifest")?;
+        fs::write(state.join(MANIFEST_FILE), toml_string).context("Unable to write the manifest")
+    }
+
+    /// Load the manifest from the state directory under root.
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join(STATE_DIR).join(MANIFEST_FILE);
+        let contents = fs::read_to_string(&path).context(format!(
+            "Unable to read {}, has anything been generated here?",
+            path.display()
+        ))?;
+        toml::from_str(&contents).context(format!("Unable to parse {}", path.display()))
+    }
+
+    /// Undo the run, most recent file first. Created files are removed, and modified files are
+    /// restored from their originals. Files that changed since they were generated are left alone,
+    /// and kept in the manifest so undo can be run again once they're dealt with. The state
+    /// directory is removed once the manifest is empty.
+    pub fn undo(mut self, root: &Path) -> Result<Vec<Outcome>> {
+        let state = root.join(STATE_DIR);
+        let mut outcomes = Vec::new();
+        let mut remaining = Vec::new();
+
+        while let Some(entry) = self.files.pop() {
+            let target = root.join(&entry.path);
+
+            let current = if target.exists() {
+                Some(
+                    fs::read(&target)
+                        .context(format!("Unable to read {}", entry.path.display()))?,
+                )
+            } else {
+                None
+            };
+
+            if current.as_deref().map(checksum).as_ref() != Some(&entry.checksum) {
+                outcomes.push(Outcome::Changed(entry.path.clone()));
+                remaining.push(entry);
+                continue;
+            }
+
+            match (&entry.action, &entry.original) {
+                (Action::Modified, Some(original)) => {
+                    let original = state.join(ORIGINALS_DIR).join(original);
+                    fs::copy(&original, &target)
+                        .context(format!("Unable to restore {}", entry.path.display()))?;
+                    outcomes.push(Outcome::Restored(entry.path));
+                }
+                _ => {
+                    fs::remove_file(&target)
+                        .context(format!("Unable to remove {}", entry.path.display()))?;
+                    remove_empty_parents(root, &entry.path);
+                    outcomes.push(Outcome::Removed(entry.path));
+                }
+            }
+        }
+
+        if remaining.is_empty() {
+            fs::remove_dir_all(&state).context("Unable to remove the state directory")?;
+        } else {
+            remaining.reverse();
+            self.files = remaining;
+            let toml_string = toml::to_string(&self).context("Failed to serialize the manifest")?;
+            fs::write(state.join(MANIFEST_FILE), toml_string)
+                .context("Unable to write the manifest")?;
+        }
+
+        Ok(outcomes)
+    }
+}
+
+/// Remove the directories a file was in, as long as they're empty, stopping at root.
+fn remove_empty_parents(root: &Path, path: &Path) {
+    for parent in path.ancestors().skip(1) {
+        if parent.as_os_str().is_empty() || fs::remove_dir(root.join(parent)).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn checksum_is_sha256() {
+        assert_eq!(
+            checksum(b"hello"),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[test]
+    fn undo_removes_created_and_restores_modified() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("existing.txt"), "original").unwrap();
+
+        let mut manifest = Manifest::new("test.spec");
+        manifest
+            .record(root, Path::new("existing.txt"), b"generated")
+            .unwrap();
+        manifest
+            .record(root, Path::new("nested/new.txt"), b"new")
+            .unwrap();
+
+        fs::write(root.join("existing.txt"), "generated").unwrap();
+        fs::create_dir_all(root.join("nested")).unwrap();
+        fs::write(root.join("nested/new.txt"), "new").unwrap();
+        manifest.save(root).unwrap();
+
+        let outcomes = Manifest::load(root).unwrap().undo(root).unwrap();
+        assert_eq!(outcomes.len(), 2);
+
+        assert_eq!(
+            fs::read_to_string(root.join("existing.txt")).unwrap(),
+            "original"
+        );
+        assert!(!root.join("nested").exists());
+        assert!(!root.join(STATE_DIR).exists());
+    }
+
+    #[test]
+    fn undo_leaves_changed_files_alone() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+
+        let mut manifest = Manifest::new("test.spec");
+        manifest
+            .record(root, Path::new("new.txt"), b"generated")
+            .unwrap();
+        fs::write(root.join("new.txt"), "edited by the user").unwrap();
+        manifest.save(root).unwrap();
+
+        let outcomes = Manifest::load(root).unwrap().undo(root).unwrap();
+        assert!(matches!(outcomes.as_slice(), [Outcome::Changed(_)]));
+
+        assert!(root.join("new.txt").exists());
+        assert_eq!(Manifest::load(root).unwrap().files.len(), 1);
+    }
+}
@@ -1,6 +1,6 @@
 use std::{
     env::current_dir,
-    path::{Component, Path},
+    path::{Component, Path, PathBuf},
 };
 
 use anyhow::{Context, Result, bail};
@@ -45,6 +45,25 @@ pub fn check_path_is_valid(path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Find a path to back up an existing file to. The backup sits next to the original with a .bak
+/// extension appended, and a number after that if an earlier backup is already there.
+pub fn backup_path(path: &Path) -> PathBuf {
+    let with_suffix = |suffix: String| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(suffix);
+        PathBuf::from(name)
+    };
+
+    let mut candidate = with_suffix(".bak".into());
+    let mut n = 1;
+    while candidate.exists() {
+        candidate = with_suffix(format!(".bak.{}", n));
+        n += 1;
+    }
+
+    candidate
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
@@ -77,4 +96,18 @@ mod tests {
         let absolute_path = PathBuf::from("/this/is/a/no/no.md");
         assert!(check_path_is_valid(&absolute_path).is_err());
     }
+
+    #[test]
+    fn backup_path_skips_existing_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = dir.path().join("README.md");
+
+        assert_eq!(backup_path(&original), dir.path().join("README.md.bak"));
+
+        std::fs::write(dir.path().join("README.md.bak"), "").unwrap();
+        assert_eq!(backup_path(&original), dir.path().join("README.md.bak.1"));
+
+        std::fs::write(dir.path().join("README.md.bak.1"), "").unwrap();
+        assert_eq!(backup_path(&original), dir.path().join("README.md.bak.2"));
+    }
 }
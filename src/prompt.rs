@@ -36,6 +36,40 @@ fn prompt_yn_with_io<R: BufRead, W: Write>(
     }
 }
 
+/// Ask the user for a value, with an optional suggested answer that's used if they don't type
+/// anything.
+pub fn prompt_value(question: &str, default_answer: Option<&str>) -> Result<String> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    prompt_value_with_io(
+        question,
+        default_answer,
+        &mut stdin.lock(),
+        &mut stdout.lock(),
+    )
+}
+
+fn prompt_value_with_io<R: BufRead, W: Write>(
+    question: &str,
+    default_answer: Option<&str>,
+    reader: &mut R,
+    writer: &mut W,
+) -> Result<String> {
+    match default_answer {
+        Some(default) => write!(writer, "{} [{}]: ", question, default)?,
+        None => write!(writer, "{}: ", question)?,
+    }
+    writer.flush()?;
+
+    let mut input = String::new();
+    reader.read_line(&mut input)?;
+
+    match input.trim() {
+        "" => Ok(default_answer.unwrap_or_default().to_string()),
+        answer => Ok(answer.to_string()),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::io::Cursor;
@@ -77,4 +111,35 @@ mod test {
         let result = prompt_yn_with_io("Test?", true, &mut reader, &mut writer).unwrap();
         assert!(!result, "Expected false but got true");
     }
+
+    #[test]
+    fn value() {
+        let input = b"my-project\n";
+        let mut reader = Cursor::new(input);
+        let mut writer = Vec::new();
+        let result =
+            prompt_value_with_io("project", Some("default"), &mut reader, &mut writer).unwrap();
+        assert_eq!(result, "my-project");
+        assert_eq!(String::from_utf8(writer).unwrap(), "project [default]: ");
+    }
+
+    #[test]
+    fn value_default() {
+        let input = b"\n";
+        let mut reader = Cursor::new(input);
+        let mut writer = Vec::new();
+        let result =
+            prompt_value_with_io("project", Some("default"), &mut reader, &mut writer).unwrap();
+        assert_eq!(result, "default");
+    }
+
+    #[test]
+    fn value_without_default() {
+        let input = b"\n";
+        let mut reader = Cursor::new(input);
+        let mut writer = Vec::new();
+        let result = prompt_value_with_io("project", None, &mut reader, &mut writer).unwrap();
+        assert_eq!(result, "");
+        assert_eq!(String::from_utf8(writer).unwrap(), "project: ");
+    }
 }
@@ -0,0 +1,308 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, bail};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Schema maps variable names to their definitions.
+pub type Schema = BTreeMap<String, Variable>;
+
+/// Variable is a richer definition of a spec variable than a plain default. Every field is
+/// optional, a definition without any of them accepts anything.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq)]
+pub struct Variable {
+    /// The TOML type the value must have.
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<VariableType>,
+
+    /// A human readable description, shown when asking for the variable on the terminal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// The value must be one of these.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub choices: Vec<toml::Value>,
+
+    /// A regular expression string values must match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+
+    /// Inclusive bounds for integers and floats.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+
+    /// The variable must have a value, an empty string doesn't count.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub required: bool,
+
+    /// The variable's value if nothing else sets it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<toml::Value>,
+}
+
+/// VariableType mirrors the types a TOML value can have.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VariableType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Datetime,
+    Array,
+    Table,
+}
+
+impl VariableType {
+    /// The type a value has.
+    pub fn of(value: &toml::Value) -> VariableType {
+        match value {
+            toml::Value::String(_) => VariableType::String,
+            toml::Value::Integer(_) => VariableType::Integer,
+            toml::Value::Float(_) => VariableType::Float,
+            toml::Value::Boolean(_) => VariableType::Boolean,
+            toml::Value::Datetime(_) => VariableType::Datetime,
+            toml::Value::Array(_) => VariableType::Array,
+            toml::Value::Table(_) => VariableType::Table,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VariableType::String => "string",
+            VariableType::Integer => "integer",
+            VariableType::Float => "float",
+            VariableType::Boolean => "boolean",
+            VariableType::Datetime => "datetime",
+            VariableType::Array => "array",
+            VariableType::Table => "table",
+        }
+    }
+
+    /// Whether the value has this type. Integers are accepted where floats are expected.
+    fn matches(&self, value: &toml::Value) -> bool {
+        matches!(
+            (self, value),
+            (VariableType::String, toml::Value::String(_))
+                | (VariableType::Integer, toml::Value::Integer(_))
+                | (VariableType::Float, toml::Value::Float(_))
+                | (VariableType::Float, toml::Value::Integer(_))
+                | (VariableType::Boolean, toml::Value::Boolean(_))
+                | (VariableType::Datetime, toml::Value::Datetime(_))
+                | (VariableType::Array, toml::Value::Array(_))
+                | (VariableType::Table, toml::Value::Table(_))
+        )
+    }
+}
+
+impl Variable {
+    /// Whether the variable is required, and doesn't have a value.
+    pub fn is_missing(&self, value: Option<&toml::Value>) -> bool {
+        let empty = match value {
+            None => true,
+            Some(toml::Value::String(s)) => s.is_empty(),
+            Some(_) => false,
+        };
+
+        self.required && empty
+    }
+
+    /// Check a value against the definition, returning a description of every way it's invalid.
+    pub fn violations(&self, value: Option<&toml::Value>) -> Result<Vec<String>> {
+        let mut violations = Vec::new();
+
+        if self.is_missing(value) {
+            violations.push("is required".to_string());
+        }
+
+        let Some(value) = value else {
+            return Ok(violations);
+        };
+
+        if let Some(kind) = &self.kind
+            && !kind.matches(value)
+        {
+            violations.push(format!(
+                "expected {}, got {} {}",
+                kind.as_str(),
+                value.type_str(),
+                value
+            ));
+        }
+
+        if !self.choices.is_empty() && !self.choices.contains(value) {
+            let choices = self
+                .choices
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            violations.push(format!("{} is not one of {}", value, choices));
+        }
+
+        if let (Some(pattern), Some(s)) = (&self.pattern, value.as_str()) {
+            let re = match Regex::new(pattern) {
+                Ok(re) => re,
+                Err(e) => bail!("{} is not a valid pattern: {}", pattern, e),
+            };
+
+            if !re.is_match(s) {
+                violations.push(format!("{} doesn't match {}", value, pattern));
+            }
+        }
+
+        let number = match value {
+            toml::Value::Integer(i) => Some(*i as f64),
+            toml::Value::Float(f) => Some(*f),
+            _ => None,
+        };
+
+        if let Some(n) = number {
+            if let Some(min) = self.min
+                && n < min
+            {
+                violations.push(format!("{} is less than the minimum of {}", value, min));
+            }
+
+            if let Some(max) = self.max
+                && n > max
+            {
+                violations.push(format!("{} is greater than the maximum of {}", value, max));
+            }
+        }
+
+        Ok(violations)
+    }
+}
+
+/// Validate variables against a schema, returning a single error that lists every violation.
+pub fn validate(schema: &Schema, variables: &toml::Table) -> Result<()> {
+    let mut violations = Vec::new();
+
+    for (name, definition) in schema {
+        for violation in definition.violations(variables.get(name))? {
+            violations.push(format!("{}: {}", name, violation));
+        }
+    }
+
+    if !violations.is_empty() {
+        bail!(
+            "{} variable(s) are invalid:\n\t{}",
+            violations.len(),
+            violations.join("\n\t")
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn port() -> Variable {
+        Variable {
+            kind: Some(VariableType::Integer),
+            min: Some(1.0),
+            max: Some(65535.0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn valid_value_has_no_violations() {
+        let violations = port()
+            .violations(Some(&toml::Value::Integer(8080)))
+            .unwrap();
+        assert!(violations.is_empty(), "{:?}", violations);
+    }
+
+    #[test]
+    fn wrong_type() {
+        let violations = port()
+            .violations(Some(&toml::Value::String("http".into())))
+            .unwrap();
+        assert_eq!(violations, vec!["expected integer, got string \"http\""]);
+    }
+
+    #[test]
+    fn out_of_range() {
+        let violations = port().violations(Some(&toml::Value::Integer(0))).unwrap();
+        assert_eq!(violations.len(), 1);
+
+        let violations = port()
+            .violations(Some(&toml::Value::Integer(70000)))
+            .unwrap();
+        assert_eq!(violations.len(), 1);
+    }
+
+    #[test]
+    fn choices() {
+        let license = Variable {
+            choices: vec!["MIT".into(), "Apache-2.0".into()],
+            ..Default::default()
+        };
+
+        assert!(license.violations(Some(&"MIT".into())).unwrap().is_empty());
+        assert_eq!(license.violations(Some(&"GPL".into())).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn pattern() {
+        let name = Variable {
+            pattern: Some("^[a-z][a-z0-9-]*$".into()),
+            ..Default::default()
+        };
+
+        assert!(
+            name.violations(Some(&"my-crate".into()))
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(name.violations(Some(&"My Crate".into())).unwrap().len(), 1);
+
+        let invalid = Variable {
+            pattern: Some("(".into()),
+            ..Default::default()
+        };
+        assert!(invalid.violations(Some(&"anything".into())).is_err());
+    }
+
+    #[test]
+    fn required() {
+        let name = Variable {
+            required: true,
+            ..Default::default()
+        };
+
+        assert_eq!(name.violations(None).unwrap(), vec!["is required"]);
+        assert_eq!(
+            name.violations(Some(&"".into())).unwrap(),
+            vec!["is required"]
+        );
+        assert!(name.violations(Some(&"set".into())).unwrap().is_empty());
+    }
+
+    #[test]
+    fn validate_reports_every_violation() {
+        let mut schema = Schema::new();
+        schema.insert("port".into(), port());
+        schema.insert(
+            "name".into(),
+            Variable {
+                required: true,
+                ..Default::default()
+            },
+        );
+
+        let mut variables = toml::Table::new();
+        variables.insert("port".into(), toml::Value::Integer(0));
+
+        let err = validate(&schema, &variables).unwrap_err().to_string();
+        assert!(err.starts_with("2 variable(s) are invalid"), "{}", err);
+        assert!(err.contains("port:"), "{}", err);
+        assert!(err.contains("name: is required"), "{}", err);
+    }
+}
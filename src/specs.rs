@@ -1,6 +1,9 @@
 use anyhow::{Context, Result, bail, ensure};
 use serde::{Deserialize, Serialize};
+
+use crate::{path::check_path_is_valid, schema::Schema};
 use std::{
+    collections::BTreeMap,
     ffi::{OsStr, OsString},
     fs,
     path::{Path, PathBuf},
@@ -8,22 +11,420 @@ use std::{
 
 /// Spec defines a full user template spec. It includes all the variables the user is setting (and
 /// their defaults), and all the files tmpl will generate.
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
 pub struct Spec {
+    /// The name of a spec in the spec directory this one builds on. Its variables, schema,
+    /// templates and hooks are inherited, and this spec's own are layered on top.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+
+    #[serde(default)]
     pub variables: toml::Table,
+
+    /// Variables derived from other variables. Each one is a minijinja expression, evaluated after
+    /// every other variable is set.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub computed: BTreeMap<String, String>,
+
+    /// Optional definitions for variables, used to validate them before generating.
+    #[serde(default, skip_serializing_if = "Schema::is_empty")]
+    pub schema: Schema,
+
+    #[serde(default)]
     pub templates: Vec<Template>,
+
+    /// Commands to run in the output directory. They only run when the user trusts the spec.
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+}
+
+impl Spec {
+    /// Layer this spec on top of the spec it extends. Variables are merged deeply, so a table in
+    /// this spec only overrides the keys it sets, and computed variables override the parent's by
+    /// name. A template replaces the parent's templates with
+    /// the same path, or removes them if it's marked with remove, and is added otherwise. Injecting
+    /// templates are always added, since they're meant to modify a file the parent writes. root is
+    /// where the parent's sources are relative to.
+    fn inherit(self, parent: Spec, root: &Path) -> Result<Spec> {
+        let mut variables = parent.variables;
+        merge_tables(&mut variables, self.variables);
+
+        let mut computed = parent.computed;
+        computed.extend(self.computed);
+
+        let mut schema = parent.schema;
+        schema.extend(self.schema);
+
+        let mut templates: Vec<Template> = parent
+            .templates
+            .into_iter()
+            .map(|mut t| {
+                t.root.get_or_insert_with(|| root.to_path_buf());
+                t
+            })
+            .collect();
+
+        for template in self.templates {
+            let path = template.path.clone();
+
+            if template.remove {
+                let before = templates.len();
+                templates.retain(|t| t.path != path);
+                ensure!(
+                    templates.len() < before,
+                    "{} is removed, but the spec it extends doesn't have it",
+                    path.display()
+                );
+                continue;
+            }
+
+            if template.inject.is_some() {
+                templates.push(template);
+                continue;
+            }
+
+            // the first of the parent's templates with the same path is replaced, and the rest
+            // are dropped
+            let mut replacement = Some(template);
+            templates = templates
+                .into_iter()
+                .filter_map(|t| {
+                    if t.path == path {
+                        replacement.take()
+                    } else {
+                        Some(t)
+                    }
+                })
+                .collect();
+            templates.extend(replacement);
+        }
+
+        let mut hooks = parent.hooks;
+        hooks.pre.extend(self.hooks.pre);
+        hooks.post.extend(self.hooks.post);
+
+        Ok(Spec {
+            extends: None,
+            variables,
+            computed,
+            schema,
+            templates,
+            hooks,
+        })
+    }
+
+    /// The spec's default variables. Defaults from the schema are used for variables that aren't
+    /// in the variables table.
+    pub fn defaults(&self) -> toml::Table {
+        let mut defaults = self.variables.clone();
+
+        for (name, definition) in &self.schema {
+            if let Some(default) = &definition.default
+                && !defaults.contains_key(name)
+            {
+                defaults.insert(name.clone(), default.clone());
+            }
+        }
+
+        defaults
+    }
 }
 
 /// Template defines an entry in the spec, that contains the contents of a file, and its path. The
 /// path can be nested arbitrarily deep, tmpl will create parent directories as necessarry.
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
 pub struct Template {
     pub path: PathBuf,
+
+    #[serde(default)]
     pub body: String,
+
+    /// A minijinja expression, evaluated against the spec's variables and the current for_each
+    /// element, if there is one. The template is only generated when it's truthy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+
+    /// A minijinja expression that evaluates to a list. The template is generated once per
+    /// element, with the element bound to the name given by `as`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub for_each: Option<String>,
+
+    /// The name each for_each element is bound to. Defaults to item.
+    #[serde(rename = "as", default, skip_serializing_if = "Option::is_none")]
+    pub item_name: Option<String>,
+
+    /// Insert the body into an existing file, instead of replacing the whole file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inject: Option<Inject>,
+
+    /// An octal file mode to set after the file is written, e.g. "0755". Ignored outside of unix.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+
+    /// Make the file executable by everyone that can read it. Ignored outside of unix.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub executable: bool,
+
+    /// A file to copy byte-for-byte instead of rendering a body, relative to the spec. That's the
+    /// spec's directory for a directory spec, or the directory a single file spec is in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<PathBuf>,
+
+    /// Base64 encoded contents to write byte-for-byte instead of rendering a body.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base64: Option<String>,
+
+    /// Write the body as-is, without rendering it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub raw: bool,
+
+    /// What to do when the file already exists. Takes precedence over --on-conflict.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_conflict: Option<ConflictPolicy>,
+
+    /// Remove the template with the same path from the spec this one extends, instead of adding
+    /// one.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub remove: bool,
+
+    /// The directory source is relative to, for templates inherited from another spec. Templates
+    /// from the spec being generated use its own directory.
+    #[serde(skip)]
+    pub root: Option<PathBuf>,
+}
+
+/// Combine several specs into one, so they can be generated together. Variables and schemas are
+/// merged in order, with later specs taking precedence, and templates and hooks are concatenated.
+/// It's an error for more than one spec to generate the same path, unless it's injecting into it.
+pub fn compose(specs: Vec<(OsString, Spec)>) -> Result<Spec> {
+    let mut generated_by: Vec<(&Path, Vec<&OsStr>)> = Vec::new();
+    for (name, spec) in &specs {
+        for template in spec.templates.iter().filter(|t| t.inject.is_none()) {
+            match generated_by
+                .iter_mut()
+                .find(|(path, _)| *path == template.path)
+            {
+                Some((_, names)) if !names.contains(&name.as_os_str()) => names.push(name),
+                Some(_) => {}
+                None => generated_by.push((&template.path, vec![name])),
+            }
+        }
+    }
+
+    let collisions: Vec<_> = generated_by
+        .iter()
+        .filter(|(_, names)| names.len() > 1)
+        .map(|(path, names)| {
+            let names: Vec<_> = names.iter().map(|n| n.to_string_lossy()).collect();
+            format!("{} ({})", path.display(), names.join(", "))
+        })
+        .collect();
+
+    ensure!(
+        collisions.is_empty(),
+        "{} path(s) are generated by more than one spec:\n\t{}",
+        collisions.len(),
+        collisions.join("\n\t")
+    );
+
+    let mut composed = Spec::default();
+    for (_, spec) in specs {
+        merge_tables(&mut composed.variables, spec.variables);
+        composed.computed.extend(spec.computed);
+        composed.schema.extend(spec.schema);
+        composed.templates.extend(spec.templates);
+        composed.hooks.pre.extend(spec.hooks.pre);
+        composed.hooks.post.extend(spec.hooks.post);
+    }
+
+    Ok(composed)
+}
+
+/// Merge overlay into base. Tables are merged key by key, recursively, and anything else in
+/// overlay replaces what's in base.
+pub fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => {
+                merge_tables(base, overlay)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Hooks are commands a spec runs around generation.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Hooks {
+    /// Run in order before anything is rendered. If one fails, nothing is generated.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre: Vec<Hook>,
+
+    /// Run in order after every template has been written.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post: Vec<Hook>,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        self.pre.is_empty() && self.post.is_empty()
+    }
+}
+
+/// Hook is a single command. It's run directly, not through a shell, and each argument is rendered
+/// with the spec's variables first.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Hook {
+    /// The program to run, followed by its arguments.
+    pub run: Vec<String>,
+
+    /// A minijinja expression, the hook only runs when it's truthy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+}
+
+/// Inject describes where a template's body goes in an existing file. Before and after take a
+/// regular expression, and the body is inserted next to the first line that matches it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Inject {
+    Before(String),
+    After(String),
+    Append,
+    Prepend,
+}
+
+/// ConflictPolicy decides what happens when a template's path already exists, and its contents
+/// differ from what would be generated.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    Skip,
+    #[default]
+    Overwrite,
+    Prompt,
+    Backup,
+    Fail,
+}
+
+impl ConflictPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Skip => "skip",
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::Prompt => "prompt",
+            ConflictPolicy::Backup => "backup",
+            ConflictPolicy::Fail => "fail",
+        }
+    }
+}
+
+/// The manifest file of a directory spec.
+pub const SPEC_MANIFEST: &str = "spec.toml";
+
+/// The directory in a directory spec that holds its template files.
+pub const TEMPLATES_DIR: &str = "templates";
+
+/// The directory in the spec directory that holds partials shared by every spec.
+pub const PARTIALS_DIR: &str = "partials";
+
+/// Whether the path is a directory spec, a directory with a spec.toml manifest in it.
+fn is_spec_dir(path: &Path) -> bool {
+    path.is_dir() && path.join(SPEC_MANIFEST).is_file()
+}
+
+/// Read a directory spec. The spec.toml manifest is read like a single file spec, then every file
+/// under the templates directory becomes a template, with its path relative to the templates
+/// directory. If the manifest already has a template with the same path and no body, the file is
+/// used as its body instead, so the manifest can still set options for it. Files that aren't
+/// valid UTF-8 are copied byte-for-byte.
+pub fn read_spec_dir(dir: &Path) -> Result<Spec> {
+    let manifest = dir.join(SPEC_MANIFEST);
+    let contents =
+        fs::read_to_string(&manifest).context(format!("Unable to read {}", manifest.display()))?;
+    let mut spec: Spec =
+        toml::from_str(&contents).context(format!("Unable to parse {}", manifest.display()))?;
+
+    let templates_dir = dir.join(TEMPLATES_DIR);
+    if !templates_dir.is_dir() {
+        return Ok(spec);
+    }
+
+    let mut files = Vec::new();
+    collect_files(&templates_dir, Path::new(""), &mut files)?;
+
+    for path in files {
+        let contents = fs::read(templates_dir.join(&path))
+            .context(format!("Unable to read template {}", path.display()))?;
+
+        let index = match spec
+            .templates
+            .iter()
+            .position(|t| t.path == path && t.body.is_empty() && t.source.is_none())
+        {
+            Some(index) => index,
+            None => {
+                spec.templates.push(Template {
+                    path: path.clone(),
+                    ..Default::default()
+                });
+                spec.templates.len() - 1
+            }
+        };
+
+        // files that aren't text can't be rendered, so they're copied as-is
+        match String::from_utf8(contents) {
+            Ok(body) => spec.templates[index].body = body,
+            Err(_) => spec.templates[index].source = Some(Path::new(TEMPLATES_DIR).join(path)),
+        }
+    }
+
+    Ok(spec)
+}
+
+/// Recursively collect the paths of every file under dir, relative to it, in a stable order.
+fn collect_files(dir: &Path, prefix: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .context(format!("Unable to read {}", dir.display()))?
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let path = prefix.join(entry.file_name());
+        if entry.path().is_dir() {
+            collect_files(&entry.path(), &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Recursively copy a directory.
+fn copy_dir(src: &Path, dst: &Path) -> Result<()> {
+    fs::create_dir_all(dst)?;
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        if entry.path().is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+
+    Ok(())
 }
 
 /// Specs represents a collection of specs, co-located in a directory. It provides programmatic
-/// access to spec files.
+/// access to spec files. A spec is either a single TOML file, or a directory with a spec.toml
+/// manifest and a tree of template files. Partials that every spec can include live next to them,
+/// in the partials directory.
+#[derive(Clone)]
 pub struct Specs {
     dir: PathBuf,
 }
@@ -53,6 +454,10 @@ impl Specs {
             bail!("{} is not a valid spec name", name.display());
         }
 
+        if name == PARTIALS_DIR {
+            bail!("{} is reserved for shared partials", name.display());
+        }
+
         Ok(())
     }
 
@@ -67,34 +472,97 @@ impl Specs {
         }
 
         let path = self.dir.join(name);
-        path.is_file()
+        path.is_file() || is_spec_dir(&path)
     }
 
-    /// Read a spec file in the specs directory to a string.
+    /// Read a spec file in the specs directory to a string. For a directory spec, this is its
+    /// spec.toml manifest.
     pub fn read_to_string(&self, name: &OsStr) -> Result<String> {
-        self.validate_spec_name(name)
+        let path = self
+            .safe_get_spec_path(name)
             .context("Unable to read spec to string")?;
 
-        let path = self.dir.join(name);
         fs::read_to_string(path).context(format!("Failed to read {} to string", name.display()))
     }
 
-    /// Get the full path to a spec file, which will return an error if it doesn't exist
+    /// Get the full path to a spec file, which will return an error if it doesn't exist. For a
+    /// directory spec, this is the path to its spec.toml manifest.
     pub fn safe_get_spec_path(&self, name: &OsStr) -> Result<PathBuf> {
         self.validate_spec_name(name)
             .context("Unable to safely get the full path to the spec")?;
 
         let path = self.dir.join(name);
         ensure!(path.exists(), "{} doesn't exist", name.display());
-        ensure!(path.is_file(), "{} is not a file", name.display());
+
+        if is_spec_dir(&path) {
+            return Ok(path.join(SPEC_MANIFEST));
+        }
+
+        ensure!(
+            path.is_file(),
+            "{} is not a file, or a directory with a {}",
+            name.display(),
+            SPEC_MANIFEST
+        );
         Ok(path)
     }
 
-    /// Deserialize a spec file and return it as a Spec struct.
+    /// Deserialize a spec and return it as a Spec struct. If it extends another spec, the spec it
+    /// extends is read too, and the two are merged.
     pub fn read_spec(&self, name: &OsStr) -> Result<Spec> {
+        let spec = self.read_spec_without_parents(name)?;
+        self.resolve_with_chain(spec, &mut vec![name.to_string_lossy().into_owned()])
+    }
+
+    /// Merge a spec that was read from somewhere else, like a file given with --file, with the spec
+    /// it extends, if it extends one.
+    pub fn resolve(&self, spec: Spec) -> Result<Spec> {
+        self.resolve_with_chain(spec, &mut Vec::new())
+    }
+
+    /// Resolve a spec's parents, recursively. chain holds the names of every spec that's been read
+    /// so far, to detect specs that end up extending themselves.
+    fn resolve_with_chain(&self, spec: Spec, chain: &mut Vec<String>) -> Result<Spec> {
+        let Some(parent_name) = spec.extends.clone() else {
+            if let Some(removed) = spec.templates.iter().find(|t| t.remove) {
+                bail!(
+                    "{} is removed, but the spec doesn't extend another one",
+                    removed.path.display()
+                );
+            }
+            return Ok(spec);
+        };
+
+        let cycle = chain.contains(&parent_name);
+        chain.push(parent_name.clone());
+        ensure!(!cycle, "Specs extend each other: {}", chain.join(" -> "));
+
+        let parent = self
+            .read_spec_without_parents(OsStr::new(&parent_name))
+            .and_then(|parent| self.resolve_with_chain(parent, chain))
+            .context(format!("Unable to extend {}", parent_name))?;
+
+        let path = self.dir.join(&parent_name);
+        let root = if is_spec_dir(&path) {
+            path
+        } else {
+            self.dir.clone()
+        };
+
+        spec.inherit(parent, &root)
+            .context(format!("Unable to extend {}", parent_name))
+    }
+
+    /// Deserialize a spec as-is, without reading the spec it extends.
+    fn read_spec_without_parents(&self, name: &OsStr) -> Result<Spec> {
         self.validate_spec_name(name)
             .context("Unable to read spec")?;
 
+        let path = self.dir.join(name);
+        if is_spec_dir(&path) {
+            return read_spec_dir(&path);
+        }
+
         let contents = self
             .read_to_string(name)
             .context("Unable to open spec file for reading")?;
@@ -102,11 +570,37 @@ impl Specs {
         toml::from_str(&contents).context("Unable to parse template file")
     }
 
-    /// Delete a spec file.
+    /// Return the directory shared partials are kept in.
+    pub fn partials_dir(&self) -> PathBuf {
+        self.dir.join(PARTIALS_DIR)
+    }
+
+    /// Read a shared partial, by its path relative to the partials directory. Returns None if
+    /// there's no such partial.
+    pub fn read_partial(&self, name: &str) -> Result<Option<String>> {
+        check_path_is_valid(Path::new(name))
+            .context(format!("{} is not a valid partial name", name))?;
+
+        let path = self.partials_dir().join(name);
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        fs::read_to_string(&path)
+            .map(Some)
+            .context(format!("Unable to read partial {}", name))
+    }
+
+    /// Delete a spec file, or a directory spec and everything in it.
     pub fn delete_spec(&self, name: &OsStr) -> Result<()> {
         self.validate_spec_name(name)
             .context("Unable to delete spec")?;
 
+        let path = self.dir.join(name);
+        if is_spec_dir(&path) {
+            return fs::remove_dir_all(path).context("Failed to delete spec");
+        }
+
         let path = self.safe_get_spec_path(name)?;
         fs::remove_file(path).context("Failed to delete spec")
     }
@@ -151,16 +645,33 @@ impl Specs {
             "Cannot copy a spec to itself, this would result in truncation"
         );
 
+        // only specs are replaced, anything else in the specs directory isn't tmpl's to delete
+        ensure!(
+            !dst.exists() || self.exists(dst_name),
+            "{} exists and isn't a spec, refusing to replace it",
+            dst.display()
+        );
+
+        if is_spec_dir(&dst) {
+            fs::remove_dir_all(&dst)?;
+        } else if dst.is_file() && is_spec_dir(&src) {
+            fs::remove_file(&dst)?;
+        }
+
+        if is_spec_dir(&src) {
+            return copy_dir(&src, &dst);
+        }
+
         fs::copy(src, dst)?;
         Ok(())
     }
 
-    /// Get all of the specs in the spec directory
+    /// Get all of the specs in the spec directory, both files and directory specs
     pub fn get_all_specs(&self) -> Result<Vec<OsString>> {
         let entries = fs::read_dir(&self.dir)?
             .filter_map(|r| {
                 if let Ok(e) = r {
-                    if e.path().is_file() {
+                    if e.path().is_file() || is_spec_dir(&e.path()) {
                         Some(e.file_name())
                     } else {
                         None
@@ -187,6 +698,7 @@ mod tests {
         Spec {
             variables: toml::map::Map::new(),
             templates: vec![],
+            ..Default::default()
         }
     }
 
@@ -294,6 +806,7 @@ mod tests {
         spec.templates.push(Template {
             path: "README.md".into(),
             body: "Hello, world!".into(),
+            ..Default::default()
         });
 
         specs.write_spec(&spec_name, &spec).unwrap();
@@ -388,4 +901,223 @@ mod tests {
             assert!(spec_names.contains(spec_name));
         }
     }
+
+    /// Create a directory spec with a manifest and a couple of template files.
+    fn write_spec_dir(specs: &Specs, name: &str) -> PathBuf {
+        let dir = specs.dir.join(name);
+        fs::create_dir_all(dir.join(TEMPLATES_DIR).join("src")).unwrap();
+
+        fs::write(
+            dir.join(SPEC_MANIFEST),
+            r#"
+[variables]
+name = "world"
+
+[[templates]]
+path = "src/main.rs"
+mode = "0755"
+"#,
+        )
+        .unwrap();
+
+        fs::write(
+            dir.join(TEMPLATES_DIR).join("README.md"),
+            "Hello, {{ name }}",
+        )
+        .unwrap();
+        fs::write(dir.join(TEMPLATES_DIR).join("src/main.rs"), "fn main() {}").unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn read_spec_dir() {
+        let dir = tempdir().unwrap();
+        let specs = Specs::new(dir.path()).unwrap();
+        write_spec_dir(&specs, "dir.spec");
+
+        let name = OsString::from("dir.spec");
+        assert!(specs.exists(&name));
+        assert_eq!(
+            specs.safe_get_spec_path(&name).unwrap(),
+            dir.path().join("dir.spec").join(SPEC_MANIFEST)
+        );
+
+        let spec = specs.read_spec(&name).unwrap();
+        assert_eq!(spec.templates.len(), 2);
+
+        // the manifest's entry picks up the file as its body, and keeps its options
+        assert_eq!(spec.templates[0].path, PathBuf::from("src/main.rs"));
+        assert_eq!(spec.templates[0].body, "fn main() {}");
+        assert_eq!(spec.templates[0].mode.as_deref(), Some("0755"));
+
+        assert_eq!(spec.templates[1].path, PathBuf::from("README.md"));
+        assert_eq!(spec.templates[1].body, "Hello, {{ name }}");
+    }
+
+    #[test]
+    fn read_partial() {
+        let dir = tempdir().unwrap();
+        let specs = Specs::new(dir.path()).unwrap();
+
+        fs::create_dir_all(specs.partials_dir().join("licenses")).unwrap();
+        fs::write(specs.partials_dir().join("licenses/mit.txt"), "MIT").unwrap();
+
+        assert_eq!(
+            specs.read_partial("licenses/mit.txt").unwrap().as_deref(),
+            Some("MIT")
+        );
+        assert_eq!(specs.read_partial("licenses/gpl.txt").unwrap(), None);
+        assert!(specs.read_partial("../secret.txt").is_err());
+        assert!(specs.read_partial("/etc/passwd").is_err());
+
+        // the partials directory isn't a spec, and can't be replaced by one
+        assert!(specs.get_all_specs().unwrap().is_empty());
+        assert!(
+            specs
+                .write_spec(OsStr::new(PARTIALS_DIR), &dummy_spec())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn spec_dirs_can_be_listed_copied_and_deleted() {
+        let dir = tempdir().unwrap();
+        let specs = Specs::new(dir.path()).unwrap();
+        write_spec_dir(&specs, "dir.spec");
+        specs
+            .write_spec(OsStr::new("file.spec"), &dummy_spec())
+            .unwrap();
+
+        // directories without a manifest aren't specs
+        fs::create_dir(dir.path().join("not.a.spec")).unwrap();
+
+        let mut all_specs = specs.get_all_specs().unwrap();
+        all_specs.sort();
+        assert_eq!(all_specs, vec!["dir.spec", "file.spec"]);
+
+        let src = OsString::from("dir.spec");
+        let dst = OsString::from("copy.spec");
+        specs.copy(&src, &dst).unwrap();
+        assert_eq!(
+            specs.read_spec(&src).unwrap(),
+            specs.read_spec(&dst).unwrap()
+        );
+
+        specs.delete_spec(&src).unwrap();
+        assert!(!dir.path().join("dir.spec").exists());
+        assert!(specs.exists(&dst));
+    }
+
+    fn write_toml(specs: &Specs, name: &str, contents: &str) {
+        fs::write(specs.dir.join(name), contents).unwrap();
+    }
+
+    #[test]
+    fn extends() {
+        let dir = tempdir().unwrap();
+        let specs = Specs::new(dir.path()).unwrap();
+
+        write_toml(
+            &specs,
+            "base",
+            r#"
+[variables]
+name = "base"
+license = "MIT"
+package = { edition = "2024", authors = ["me"] }
+
+[[templates]]
+path = "README.md"
+body = "base readme"
+
+[[templates]]
+path = "LICENSE"
+body = "{{ license }}"
+
+[[templates]]
+path = ".gitignore"
+body = "target"
+"#,
+        );
+        write_toml(
+            &specs,
+            "child",
+            r#"
+extends = "base"
+
+[variables]
+name = "child"
+package = { edition = "2021" }
+
+[[templates]]
+path = "README.md"
+body = "child readme"
+
+[[templates]]
+path = "LICENSE"
+remove = true
+
+[[templates]]
+path = ".gitignore"
+inject = "append"
+body = "*.log"
+
+[[templates]]
+path = "src/main.rs"
+body = "fn main() {}"
+"#,
+        );
+
+        let spec = specs.read_spec(OsStr::new("child")).unwrap();
+        assert_eq!(spec.extends, None);
+
+        let package = spec.variables["package"].as_table().unwrap();
+        assert_eq!(spec.variables["name"].as_str(), Some("child"));
+        assert_eq!(spec.variables["license"].as_str(), Some("MIT"));
+        assert_eq!(package["edition"].as_str(), Some("2021"));
+        assert!(package.contains_key("authors"));
+
+        let templates: Vec<_> = spec
+            .templates
+            .iter()
+            .map(|t| (t.path.to_str().unwrap(), t.body.as_str()))
+            .collect();
+        assert_eq!(
+            templates,
+            vec![
+                ("README.md", "child readme"),
+                (".gitignore", "target"),
+                (".gitignore", "*.log"),
+                ("src/main.rs", "fn main() {}"),
+            ]
+        );
+
+        // inherited templates keep track of where they came from
+        assert_eq!(spec.templates[1].root.as_deref(), Some(dir.path()));
+        assert_eq!(spec.templates[3].root, None);
+    }
+
+    #[test]
+    fn extends_errors() {
+        let dir = tempdir().unwrap();
+        let specs = Specs::new(dir.path()).unwrap();
+
+        write_toml(&specs, "a", "extends = \"b\"");
+        write_toml(&specs, "b", "extends = \"c\"");
+        write_toml(&specs, "c", "extends = \"a\"");
+        let err = format!("{:#}", specs.read_spec(OsStr::new("a")).unwrap_err());
+        assert!(err.contains("a -> b -> c -> a"), "{}", err);
+
+        write_toml(&specs, "orphan", "extends = \"missing\"");
+        let err = format!("{:#}", specs.read_spec(OsStr::new("orphan")).unwrap_err());
+        assert!(err.contains("Unable to extend missing"), "{}", err);
+
+        write_toml(
+            &specs,
+            "remover",
+            "[[templates]]\npath = \"README.md\"\nremove = true",
+        );
+        assert!(specs.read_spec(OsStr::new("remover")).is_err());
+    }
 }
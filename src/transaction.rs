@@ -0,0 +1,259 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use anyhow::{Context, Result, bail};
+
+/// Transaction writes a set of files all at once, or not at all. Files are staged in a hidden
+/// directory under the root first, then moved into place when the transaction is committed. If
+/// moving any of them fails, everything that was already moved is undone, and any files that were
+/// replaced are restored.
+pub struct Transaction {
+    root: PathBuf,
+    staging: PathBuf,
+    staged: Vec<(PathBuf, PathBuf)>,
+
+    /// Set when rolling back fails, so the originals left in the staging directory aren't lost.
+    keep_staging: bool,
+}
+
+/// Undo records how to reverse a single step of a commit.
+enum Undo {
+    Remove(PathBuf),
+    Restore { path: PathBuf, original: PathBuf },
+    RemoveDir(PathBuf),
+}
+
+impl Transaction {
+    /// Start a new transaction, creating its staging directory under root.
+    pub fn new(root: &Path) -> Result<Self> {
+        let staging = root.join(format!(".tmpl-staging-{}", process::id()));
+        fs::create_dir_all(&staging).context(format!(
+            "Unable to create staging directory {}",
+            staging.display()
+        ))?;
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            staging,
+            staged: Vec::new(),
+            keep_staging: false,
+        })
+    }
+
+    /// Write the contents of a file to the staging directory. path is relative to the root.
+    pub fn stage(&mut self, path: &Path, contents: &[u8]) -> Result<()> {
+        let staged = self.staging.join(self.staged.len().to_string());
+        fs::write(&staged, contents).context(format!("Unable to stage {}", path.display()))?;
+        self.staged.push((staged, path.to_path_buf()));
+        Ok(())
+    }
+
+    /// Move every staged file into place. If anything goes wrong, the files that were already
+    /// moved are rolled back before the error is returned.
+    pub fn commit(mut self) -> Result<()> {
+        let mut undo = Vec::new();
+
+        if let Err(e) = self.move_into_place(&mut undo) {
+            return Err(self.abort(e, undo));
+        }
+
+        Ok(())
+    }
+
+    /// Roll back a failed commit. If that fails too, the staging directory is kept, since it holds
+    /// the only copy of any original that couldn't be restored.
+    fn abort(&mut self, e: anyhow::Error, undo: Vec<Undo>) -> anyhow::Error {
+        match rollback(undo) {
+            Ok(()) => e.context("Rolled back, nothing was written"),
+            Err(rollback_err) => {
+                self.keep_staging = true;
+                e.context(format!(
+                    "Rolling back failed, the original files are kept in {}: {:#}",
+                    self.staging.display(),
+                    rollback_err
+                ))
+            }
+        }
+    }
+
+    fn move_into_place(&self, undo: &mut Vec<Undo>) -> Result<()> {
+        for (i, (staged, path)) in self.staged.iter().enumerate() {
+            let target = self.root.join(path);
+
+            if let Some(parent) = target.parent() {
+                create_dirs(parent, undo)?;
+            }
+
+            if target.exists() {
+                let original = self.staging.join(format!("{}.orig", i));
+                fs::rename(&target, &original)
+                    .context(format!("Unable to move {} aside", path.display()))?;
+                undo.push(Undo::Restore {
+                    path: target.clone(),
+                    original,
+                });
+            } else {
+                undo.push(Undo::Remove(target.clone()));
+            }
+
+            fs::rename(staged, &target).context(format!("Unable to write {}", path.display()))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if self.keep_staging {
+            return;
+        }
+
+        // nothing useful can be done if this fails, the directory is just left behind
+        let _ = fs::remove_dir_all(&self.staging);
+    }
+}
+
+/// Create a directory and any missing parents, recording each one that's created so it can be
+/// removed again.
+fn create_dirs(dir: &Path, undo: &mut Vec<Undo>) -> Result<()> {
+    let mut missing: Vec<_> = dir.ancestors().take_while(|d| !d.exists()).collect();
+    missing.reverse();
+
+    for d in missing {
+        fs::create_dir(d).context(format!("Unable to create {}", d.display()))?;
+        undo.push(Undo::RemoveDir(d.to_path_buf()));
+    }
+
+    Ok(())
+}
+
+/// Reverse every step of a commit, most recent first. A step that fails doesn't stop the rest, so
+/// as much as possible is restored, and every failure is reported.
+fn rollback(undo: Vec<Undo>) -> Result<()> {
+    let mut failures = Vec::new();
+
+    for step in undo.into_iter().rev() {
+        let result = match step {
+            Undo::Remove(path) if path.exists() => {
+                fs::remove_file(&path).context(format!("Unable to remove {}", path.display()))
+            }
+            Undo::Remove(_) => Ok(()),
+            Undo::Restore { path, original } => fs::rename(&original, &path)
+                .context(format!("Unable to restore {}", path.display())),
+            Undo::RemoveDir(dir) => {
+                fs::remove_dir(&dir).context(format!("Unable to remove {}", dir.display()))
+            }
+        };
+
+        if let Err(e) = result {
+            failures.push(format!("{:#}", e));
+        }
+    }
+
+    if !failures.is_empty() {
+        bail!("{}", failures.join(", "));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn commit_writes_everything() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("existing.txt"), "old").unwrap();
+
+        let mut transaction = Transaction::new(dir.path()).unwrap();
+        transaction
+            .stage(Path::new("existing.txt"), b"new")
+            .unwrap();
+        transaction
+            .stage(Path::new("nested/file.txt"), b"nested")
+            .unwrap();
+        transaction.commit().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.path().join("existing.txt")).unwrap(),
+            "new"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("nested/file.txt")).unwrap(),
+            "nested"
+        );
+
+        // the staging directory is cleaned up
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn failed_commit_rolls_back() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("existing.txt"), "old").unwrap();
+
+        // a regular file where a directory is needed makes the last write fail
+        fs::write(dir.path().join("blocker"), "").unwrap();
+
+        let mut transaction = Transaction::new(dir.path()).unwrap();
+        transaction
+            .stage(Path::new("existing.txt"), b"new")
+            .unwrap();
+        transaction
+            .stage(Path::new("created/file.txt"), b"created")
+            .unwrap();
+        transaction
+            .stage(Path::new("blocker/file.txt"), b"never")
+            .unwrap();
+
+        assert!(transaction.commit().is_err());
+
+        assert_eq!(
+            fs::read_to_string(dir.path().join("existing.txt")).unwrap(),
+            "old"
+        );
+        assert!(!dir.path().join("created").exists());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn failed_rollback_keeps_the_originals() {
+        let dir = tempdir().unwrap();
+        let existing = dir.path().join("existing.txt");
+        fs::write(&existing, "old").unwrap();
+
+        let mut transaction = Transaction::new(dir.path()).unwrap();
+        transaction
+            .stage(Path::new("existing.txt"), b"new")
+            .unwrap();
+
+        let mut undo = Vec::new();
+        transaction.move_into_place(&mut undo).unwrap();
+
+        // a directory where the original goes back makes restoring it fail
+        fs::remove_file(&existing).unwrap();
+        fs::create_dir(&existing).unwrap();
+        fs::write(existing.join("blocker"), "").unwrap();
+
+        let staging = transaction.staging.clone();
+        let err = transaction.abort(anyhow::anyhow!("Unable to write"), undo);
+        drop(transaction);
+
+        let message = format!("{:#}", err);
+        assert!(message.contains("Rolling back failed"), "{}", message);
+        assert!(
+            message.contains(&staging.display().to_string()),
+            "{}",
+            message
+        );
+
+        assert_eq!(fs::read_to_string(staging.join("0.orig")).unwrap(), "old");
+    }
+}
@@ -0,0 +1,465 @@
+use std::{env, fs, path::Path};
+
+use anyhow::{Context, Result, bail, ensure};
+use minijinja::Value;
+use toml::value::Datetime;
+
+use crate::schema::{Schema, VariableType};
+
+/// The prefix of environment variables that set variables, unless another one is given.
+pub const DEFAULT_ENV_PREFIX: &str = "TMPL_VAR_";
+
+/// The context templates are rendered with. TOML datetimes become strings, like 1979-05-27 or
+/// 1979-05-27T07:32:00Z, since templates and the date filter know what to do with those.
+pub fn context(variables: &toml::Table) -> Value {
+    let variables: toml::Table = variables
+        .iter()
+        .map(|(k, v)| (k.clone(), datetimes_to_strings(v)))
+        .collect();
+
+    Value::from_serialize(&variables)
+}
+
+fn datetimes_to_strings(value: &toml::Value) -> toml::Value {
+    match value {
+        toml::Value::Datetime(datetime) => toml::Value::String(datetime.to_string()),
+        toml::Value::Array(items) => {
+            toml::Value::Array(items.iter().map(datetimes_to_strings).collect())
+        }
+        toml::Value::Table(table) => toml::Value::Table(
+            table
+                .iter()
+                .map(|(k, v)| (k.clone(), datetimes_to_strings(v)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Parse a value given on the command line, inferring its type. Values that look like an inline
+/// TOML array or table, like ["a", "b"] or { serde = "1" }, are parsed as one. Anything that isn't
+/// one of those, an integer, float, boolean or datetime is a string.
+pub fn parse_value(var: &str) -> toml::Value {
+    if let Some(value) = parse_inline(var) {
+        return value;
+    }
+
+    var.parse::<i64>()
+        .map(toml::Value::Integer)
+        .or_else(|_| var.parse::<f64>().map(toml::Value::Float))
+        .or_else(|_| var.parse::<bool>().map(toml::Value::Boolean))
+        .or_else(|_| var.parse::<Datetime>().map(toml::Value::Datetime))
+        .unwrap_or_else(|_| toml::Value::String(var.to_string()))
+}
+
+/// Parse a value for a variable that already has one, like a spec default, keeping its type. A
+/// string stays a string, so 1.10 isn't turned into 1.1, and 3 becomes 3.0 where a float is
+/// expected. Without a current value, the type declared in the variable's schema is used instead.
+/// An empty string is a placeholder rather than a type, so it doesn't count as a value. When the
+/// value can't have the type, or there's no type to go by, it's inferred.
+pub fn parse_like(
+    var: &str,
+    current: Option<&toml::Value>,
+    declared: Option<VariableType>,
+) -> toml::Value {
+    let kind = match current {
+        Some(toml::Value::String(s)) if s.is_empty() => declared,
+        Some(current) => Some(VariableType::of(current)),
+        None => declared,
+    };
+
+    match kind {
+        Some(kind) => coerce(var, kind).unwrap_or_else(|_| parse_value(var)),
+        None => parse_value(var),
+    }
+}
+
+/// The type a variable's schema definition declares, if any.
+pub fn declared_type(schema: &Schema, name: &str) -> Option<VariableType> {
+    schema.get(name).and_then(|definition| definition.kind)
+}
+
+/// Parse a value as a specific type. Arrays and tables are written as inline TOML.
+pub fn coerce(var: &str, kind: VariableType) -> Result<toml::Value> {
+    let value = match kind {
+        VariableType::String => Some(toml::Value::String(var.to_string())),
+        VariableType::Integer => var.parse().ok().map(toml::Value::Integer),
+        VariableType::Float => var.parse().ok().map(toml::Value::Float),
+        VariableType::Boolean => var.parse().ok().map(toml::Value::Boolean),
+        VariableType::Datetime => var.parse().ok().map(toml::Value::Datetime),
+        VariableType::Array | VariableType::Table => {
+            parse_inline(var).filter(|value| VariableType::of(value) == kind)
+        }
+    };
+
+    value.with_context(|| format!("\"{}\" isn't a valid {}", var, kind.as_str()))
+}
+
+/// Split a type annotation off a key, like version:str. The annotation can be any schema type, or
+/// one of the short names str, int and bool. Anything else after a colon is part of the key, so
+/// a:b is just a key.
+pub fn split_annotation(key: &str) -> (&str, Option<VariableType>) {
+    let Some((name, annotation)) = key.rsplit_once(':') else {
+        return (key, None);
+    };
+
+    let kind = match annotation {
+        "str" | "string" => VariableType::String,
+        "int" | "integer" => VariableType::Integer,
+        "float" => VariableType::Float,
+        "bool" | "boolean" => VariableType::Boolean,
+        "datetime" => VariableType::Datetime,
+        "array" => VariableType::Array,
+        "table" => VariableType::Table,
+        _ => return (key, None),
+    };
+
+    (name, Some(kind))
+}
+
+/// Parse an inline TOML array or table.
+fn parse_inline(var: &str) -> Option<toml::Value> {
+    let trimmed = var.trim();
+    if !trimmed.starts_with(['[', '{']) {
+        return None;
+    }
+
+    let mut document: toml::Table = toml::from_str(&format!("value = {}", trimmed)).ok()?;
+    document.remove("value")
+}
+
+/// Set a variable by its key. Keys with dots in them, like db.port, set a key in a nested table,
+/// creating the tables along the way. Anything in the way that isn't a table is replaced.
+pub fn set_path(variables: &mut toml::Table, key: &str, value: toml::Value) {
+    let segments: Vec<_> = key.split('.').collect();
+
+    // a key like .port or db..port can't be a path, so it's used as-is
+    let (last, parents) = match segments.split_last() {
+        Some((last, parents)) if segments.iter().all(|s| !s.is_empty()) => (*last, parents),
+        _ => {
+            variables.insert(key.to_string(), value);
+            return;
+        }
+    };
+
+    let mut table = variables;
+    for segment in parents {
+        let entry = table
+            .entry(segment.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+
+        if !entry.is_table() {
+            *entry = toml::Value::Table(toml::Table::new());
+        }
+
+        table = match entry {
+            toml::Value::Table(nested) => nested,
+            _ => unreachable!("the entry was just made a table"),
+        };
+    }
+
+    table.insert(last.to_string(), value);
+}
+
+/// Get a variable by its key, where dots in the key look into nested tables like in set_path.
+pub fn get_path<'a>(variables: &'a toml::Table, key: &str) -> Option<&'a toml::Value> {
+    if let Some(value) = variables.get(key) {
+        return Some(value);
+    }
+
+    let (first, rest) = key.split_once('.')?;
+    match variables.get(first)? {
+        toml::Value::Table(nested) => get_path(nested, rest),
+        _ => None,
+    }
+}
+
+/// The name of the top level variable a key sets, e.g. db for db.port or version for version:str.
+pub fn root_name(key: &str) -> &str {
+    let (name, _) = split_annotation(key);
+    name.split('.').next().unwrap_or(name)
+}
+
+/// Collect variables from the environment. Every environment variable starting with prefix sets
+/// the variable named by the rest of it, e.g. TMPL_VAR_project=demo sets project. Like values given
+/// with -o, a value keeps the type of the variable in defaults, or the one declared in schema, or
+/// has its type inferred.
+pub fn from_env(prefix: &str, defaults: &toml::Table, schema: &Schema) -> Result<toml::Table> {
+    ensure!(
+        !prefix.is_empty(),
+        "The environment variable prefix can't be empty"
+    );
+
+    // variables that aren't valid unicode can't be tmpl variables anyway
+    let vars =
+        env::vars_os().filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)));
+    Ok(with_prefix(prefix, vars, defaults, schema))
+}
+
+/// Collect the variables that start with prefix, without it.
+fn with_prefix(
+    prefix: &str,
+    vars: impl IntoIterator<Item = (String, String)>,
+    defaults: &toml::Table,
+    schema: &Schema,
+) -> toml::Table {
+    vars.into_iter()
+        .filter_map(|(key, value)| {
+            let name = key.strip_prefix(prefix)?;
+            let value = parse_like(&value, defaults.get(name), declared_type(schema, name));
+            (!name.is_empty()).then(|| (name.to_string(), value))
+        })
+        .collect()
+}
+
+/// Read variables from a file. The format is picked by the file's extension, and can be TOML,
+/// JSON or YAML. Whatever the format, the file has to be a table of variables at the top level.
+pub fn read_file(path: &Path) -> Result<toml::Table> {
+    let contents =
+        fs::read_to_string(path).context(format!("Unable to read {}", path.display()))?;
+
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+
+    let variables = match extension.as_deref() {
+        Some("toml") => toml::from_str(&contents).map_err(anyhow::Error::from),
+        Some("json") => serde_json::from_str(&contents).map_err(anyhow::Error::from),
+        Some("yaml" | "yml") => serde_yaml_ng::from_str(&contents).map_err(anyhow::Error::from),
+        _ => bail!(
+            "{} isn't a TOML, JSON or YAML file, judging by its extension",
+            path.display()
+        ),
+    };
+
+    variables.context(format!("Unable to parse variables from {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn every_format_reads_the_same() {
+        let dir = tempdir().unwrap();
+        let files = [
+            (
+                "vars.toml",
+                "name = \"demo\"\nport = 8080\n[db]\nhost = \"localhost\"\n",
+            ),
+            (
+                "vars.json",
+                r#"{"name": "demo", "port": 8080, "db": {"host": "localhost"}}"#,
+            ),
+            (
+                "vars.yaml",
+                "name: demo\nport: 8080\ndb:\n  host: localhost\n",
+            ),
+            (
+                "VARS.YML",
+                "name: demo\nport: 8080\ndb: {host: localhost}\n",
+            ),
+        ];
+
+        let mut expected = toml::Table::new();
+        expected.insert("name".into(), "demo".into());
+        expected.insert("port".into(), 8080.into());
+        let mut db = toml::Table::new();
+        db.insert("host".into(), "localhost".into());
+        expected.insert("db".into(), db.into());
+
+        for (name, contents) in files {
+            let path = dir.path().join(name);
+            fs::write(&path, contents).unwrap();
+            assert_eq!(read_file(&path).unwrap(), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn environment_variables_with_the_prefix() {
+        let vars = [
+            ("TMPL_VAR_project", "demo"),
+            ("TMPL_VAR_port", "8080"),
+            ("TMPL_VAR_", "no name"),
+            ("HOME", "/home/me"),
+            ("MY_TMPL_VAR_other", "nope"),
+            ("TMPL_VAR_version", "1.10"),
+            ("TMPL_VAR_zip", "007"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+
+        // values keep the type of their default, like -o values
+        let defaults: toml::Table = toml::from_str("version = '1.0'").unwrap();
+        let variables = with_prefix(DEFAULT_ENV_PREFIX, vars, &defaults, &Schema::new());
+
+        assert_eq!(variables.len(), 4);
+        assert_eq!(variables["project"].as_str(), Some("demo"));
+        assert_eq!(variables["port"].as_integer(), Some(8080));
+        assert_eq!(variables["version"].as_str(), Some("1.10"));
+        assert_eq!(variables["zip"].as_integer(), Some(7));
+
+        assert!(from_env("", &defaults, &Schema::new()).is_err());
+    }
+
+    #[test]
+    fn inline_values() {
+        let features = parse_value(r#"["a", "b"]"#);
+        assert_eq!(features, toml::Value::Array(vec!["a".into(), "b".into()]));
+
+        let deps = parse_value(r#"{ serde = "1", toml = { version = "0.9" } }"#);
+        assert_eq!(deps["serde"].as_str(), Some("1"));
+        assert_eq!(deps["toml"]["version"].as_str(), Some("0.9"));
+
+        // things that only look like arrays or tables are still strings
+        assert_eq!(
+            parse_value("[not toml"),
+            toml::Value::String("[not toml".into())
+        );
+        assert_eq!(parse_value("{}}"), toml::Value::String("{}}".into()));
+    }
+
+    #[test]
+    fn nested_keys() {
+        let mut variables = toml::Table::new();
+        variables.insert("name".into(), "demo".into());
+        variables.insert("scalar".into(), 1.into());
+
+        set_path(&mut variables, "db.port", 5432.into());
+        set_path(&mut variables, "db.host", "localhost".into());
+        set_path(&mut variables, "a.b.c", true.into());
+        set_path(&mut variables, "scalar.nested", 2.into());
+        set_path(&mut variables, ".odd", 3.into());
+
+        assert_eq!(variables["name"].as_str(), Some("demo"));
+        assert_eq!(variables["db"]["port"].as_integer(), Some(5432));
+        assert_eq!(variables["db"]["host"].as_str(), Some("localhost"));
+        assert_eq!(variables["a"]["b"]["c"].as_bool(), Some(true));
+        assert_eq!(variables["scalar"]["nested"].as_integer(), Some(2));
+        assert_eq!(variables[".odd"].as_integer(), Some(3));
+
+        assert_eq!(root_name("db.port"), "db");
+        assert_eq!(root_name("name"), "name");
+    }
+
+    #[test]
+    fn values_keep_the_type_of_the_current_value() {
+        let string = toml::Value::String("1.0".into());
+        let empty = toml::Value::String(String::new());
+        let float = toml::Value::Float(0.5);
+        let integer = toml::Value::Integer(1);
+        let boolean = toml::Value::Boolean(false);
+        let list = toml::Value::Array(vec![]);
+
+        // strings stay strings, whatever they look like
+        assert_eq!(parse_like("1.10", Some(&string), None), "1.10".into());
+        assert_eq!(parse_like("007", Some(&string), None), "007".into());
+        assert_eq!(parse_like("true", Some(&string), None), "true".into());
+
+        // integers are widened where floats are expected
+        assert_eq!(parse_like("3", Some(&float), None), 3.0.into());
+        assert_eq!(parse_like("007", Some(&integer), None), 7.into());
+        assert_eq!(parse_like("true", Some(&boolean), None), true.into());
+        assert_eq!(parse_like(r#"["a"]"#, Some(&list), None), vec!["a"].into());
+
+        // values that can't have the current type, or have nothing to go by, are inferred
+        assert_eq!(parse_like("1.5", Some(&integer), None), 1.5.into());
+        assert_eq!(parse_like("yes", Some(&boolean), None), "yes".into());
+        assert_eq!(parse_like("1.10", Some(&empty), None), 1.1.into());
+        assert_eq!(parse_like("1.10", None, None), 1.1.into());
+
+        // without a value, the type declared in the schema is used
+        let declared = Some(VariableType::String);
+        assert_eq!(parse_like("1.10", None, declared), "1.10".into());
+        assert_eq!(parse_like("1.10", Some(&empty), declared), "1.10".into());
+        assert_eq!(parse_like("7", Some(&float), declared), 7.0.into());
+        assert_eq!(parse_like("3", None, Some(VariableType::Float)), 3.0.into());
+    }
+
+    #[test]
+    fn values_coerced_to_a_type() {
+        use VariableType::*;
+
+        assert_eq!(coerce("007", String).unwrap(), "007".into());
+        assert_eq!(coerce("007", Integer).unwrap(), 7.into());
+        assert_eq!(coerce("1", Float).unwrap(), 1.0.into());
+        assert_eq!(coerce("false", Boolean).unwrap(), false.into());
+        assert_eq!(
+            coerce("1979-05-27", Datetime).unwrap(),
+            toml::Value::Datetime("1979-05-27".parse().unwrap())
+        );
+        assert_eq!(coerce("[1, 2]", Array).unwrap(), vec![1, 2].into());
+        assert_eq!(coerce("{ a = 1 }", Table).unwrap()["a"], 1.into());
+
+        let err = coerce("1.10", Integer).unwrap_err();
+        assert_eq!(err.to_string(), "\"1.10\" isn't a valid integer");
+        assert!(coerce("yes", Boolean).is_err());
+        assert!(coerce("soon", Datetime).is_err());
+        assert!(coerce("{ a = 1 }", Array).is_err());
+        assert!(coerce("[1, 2]", Table).is_err());
+    }
+
+    #[test]
+    fn annotations() {
+        assert_eq!(split_annotation("version"), ("version", None));
+        assert_eq!(
+            split_annotation("version:str"),
+            ("version", Some(VariableType::String))
+        );
+        assert_eq!(
+            split_annotation("db.port:integer"),
+            ("db.port", Some(VariableType::Integer))
+        );
+        assert_eq!(split_annotation("a:b"), ("a:b", None));
+        assert_eq!(
+            split_annotation("a:b:int"),
+            ("a:b", Some(VariableType::Integer))
+        );
+
+        assert_eq!(root_name("version:str"), "version");
+        assert_eq!(root_name("a:b"), "a:b");
+    }
+
+    #[test]
+    fn nested_lookups() {
+        let variables: toml::Table = toml::from_str("name = 'x'\n[db]\nport = 1").unwrap();
+
+        assert_eq!(get_path(&variables, "name"), Some(&"x".into()));
+        assert_eq!(get_path(&variables, "db.port"), Some(&1.into()));
+        assert_eq!(get_path(&variables, "db.host"), None);
+        assert_eq!(get_path(&variables, "name.first"), None);
+    }
+
+    #[test]
+    fn datetimes_are_strings_in_the_context() {
+        let variables: toml::Table =
+            toml::from_str("released = 1979-05-27\n[nested]\ntimes = [07:32:00]").unwrap();
+
+        let context = context(&variables);
+        let released = context.get_attr("released").unwrap();
+        assert_eq!(released.as_str(), Some("1979-05-27"));
+
+        let times = context
+            .get_attr("nested")
+            .unwrap()
+            .get_attr("times")
+            .unwrap();
+        let first = times.get_item(&Value::from(0)).unwrap();
+        assert_eq!(first.as_str(), Some("07:32:00"));
+    }
+
+    #[test]
+    fn unknown_formats_and_non_tables_are_errors() {
+        let dir = tempdir().unwrap();
+
+        let ini = dir.path().join("vars.ini");
+        fs::write(&ini, "name=demo").unwrap();
+        assert!(read_file(&ini).is_err());
+
+        let list = dir.path().join("vars.json");
+        fs::write(&list, "[1, 2, 3]").unwrap();
+        assert!(read_file(&list).is_err());
+    }
+}
@@ -51,3 +51,31 @@ fn cp_overwrite_results_in_error() {
     let result = workspace.specs.copy(&name, &name);
     assert!(result.is_err());
 }
+
+#[test]
+fn cp_refuses_to_replace_directories_that_arent_specs() {
+    let src_name = OsString::from("test.src");
+    let mut workspace = TestWorkspace::new();
+
+    let new_args = NewArgs {
+        name: src_name.clone(),
+        edit: false,
+    };
+    cmd::new(&workspace.specs, new_args, &mut workspace.io).unwrap();
+
+    let notes = workspace.specs.dir().join("notes");
+    std::fs::create_dir(&notes).unwrap();
+    std::fs::write(notes.join("todo.txt"), "keep me").unwrap();
+
+    let cp_args = CpArgs {
+        source: src_name,
+        dest: OsString::from("notes"),
+        skip_prompt: true,
+    };
+
+    assert!(cmd::cp(&workspace.specs, cp_args).is_err());
+    assert_eq!(
+        std::fs::read_to_string(notes.join("todo.txt")).unwrap(),
+        "keep me"
+    );
+}
@@ -9,6 +9,10 @@ use std::path::PathBuf;
 use serial_test::serial;
 use tmpl::cli::GenArgs;
 use tmpl::cmd;
+use tmpl::schema::{Variable, VariableType};
+use tmpl::specs::ConflictPolicy;
+use tmpl::specs::Hook;
+use tmpl::specs::Inject;
 use tmpl::specs::Spec;
 use tmpl::specs::Template;
 
@@ -27,11 +31,13 @@ fn create_test_spec() -> Spec {
     let mut spec = Spec {
         variables: toml::map::Map::new(),
         templates: Vec::new(),
+        ..Default::default()
     };
 
     let template = Template {
         path: PathBuf::from(TEMPLATE_PATH),
         body: String::from(TEMPLATE_BODY),
+        ..Default::default()
     };
 
     spec.templates.push(template);
@@ -52,10 +58,11 @@ fn generate_with_spec_name() {
     workspace.specs.write_spec(&spec_name, &spec).unwrap();
 
     let gen_args = GenArgs {
-        name: spec_name.clone().into(),
+        names: vec![spec_name.clone()],
         options: vec![],
         workdir: Some(workspace.dir.path().into()),
         spec_file: None,
+        ..Default::default()
     };
 
     cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
@@ -83,10 +90,11 @@ fn generate_with_spec_file() {
     fs::write(&spec_file, serialized_spec).unwrap();
 
     let gen_args = GenArgs {
-        name: None,
+        names: vec![],
         options: vec![],
         workdir: Some(workspace.dir.path().into()),
         spec_file: Some(spec_file),
+        ..Default::default()
     };
 
     cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
@@ -112,10 +120,11 @@ fn generate_with_options() {
     workspace.specs.write_spec(&spec_name, &spec).unwrap();
 
     let gen_args = GenArgs {
-        name: spec_name.clone().into(),
+        names: vec![spec_name.clone()],
         options: vec!["name".into(), "bill".into()],
         workdir: Some(workspace.dir.path().into()),
         spec_file: None,
+        ..Default::default()
     };
 
     cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
@@ -136,10 +145,11 @@ Hello, bill
 fn neither_name_nor_file_returns_error() {
     let mut workspace = TestWorkspace::new();
     let gen_args = GenArgs {
-        name: None,
+        names: vec![],
         options: vec![],
         workdir: None,
         spec_file: None,
+        ..Default::default()
     };
     assert!(cmd::generate(&workspace.specs, gen_args, &mut workspace.io).is_err());
 }
@@ -149,10 +159,1313 @@ fn neither_name_nor_file_returns_error() {
 fn both_name_and_file_returns_error() {
     let mut workspace = TestWorkspace::new();
     let gen_args = GenArgs {
-        name: Some("some.name".into()),
+        names: vec!["some.name".into()],
         options: vec![],
         workdir: None,
         spec_file: Some("some/path.toml".into()),
+        ..Default::default()
     };
     assert!(cmd::generate(&workspace.specs, gen_args, &mut workspace.io).is_err());
 }
+
+#[test]
+#[serial]
+fn dry_run_writes_nothing() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let spec = create_test_spec();
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name.clone()],
+        workdir: Some(workspace.dir.path().into()),
+        dry_run: true,
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    assert!(!workspace.dir.path().join(TEMPLATE_PATH).exists());
+
+    let output = String::from_utf8_lossy(workspace.io.stdout());
+    assert!(
+        output.contains("new"),
+        "expected a new status, got:\n{}",
+        output
+    );
+    assert!(output.contains(TEMPLATE_PATH));
+}
+
+#[test]
+#[serial]
+fn dry_run_reports_existing_files() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.templates.push(Template {
+        path: PathBuf::from("unchanged.txt"),
+        body: String::from("same"),
+        ..Default::default()
+    });
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    fs::write(
+        workspace.dir.path().join(TEMPLATE_PATH),
+        "# Template\nHello, old\n",
+    )
+    .unwrap();
+    fs::write(workspace.dir.path().join("unchanged.txt"), "same").unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name.clone()],
+        workdir: Some(workspace.dir.path().into()),
+        dry_run: true,
+        diff: true,
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    // the existing file is left alone
+    let contents = read_to_string(workspace.dir.path().join(TEMPLATE_PATH)).unwrap();
+    assert_eq!(contents, "# Template\nHello, old\n");
+
+    let output = String::from_utf8_lossy(workspace.io.stdout());
+    assert!(output.contains("would overwrite"), "got:\n{}", output);
+    assert!(output.contains("unchanged"), "got:\n{}", output);
+    assert!(output.contains("-Hello, old"), "got:\n{}", output);
+    assert!(output.contains("+Hello, testing"), "got:\n{}", output);
+
+    // the workdir is absolute, but the diff headers are relative to it
+    assert!(output.contains("--- a/README.md\n"), "got:\n{}", output);
+    assert!(output.contains("+++ b/README.md\n"), "got:\n{}", output);
+}
+
+/// Write the test spec and an existing README.md that conflicts with it, returning the name of the
+/// spec.
+fn setup_conflict(workspace: &TestWorkspace, policy: Option<ConflictPolicy>) -> OsString {
+    let spec_name = OsString::from("test.spec");
+
+    let mut spec = create_test_spec();
+    spec.templates[0].on_conflict = policy;
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    fs::write(workspace.dir.path().join(TEMPLATE_PATH), "existing").unwrap();
+
+    spec_name
+}
+
+#[test]
+#[serial]
+fn on_conflict_skip_keeps_existing_file() {
+    let mut workspace = TestWorkspace::new();
+    let spec_name = setup_conflict(&workspace, None);
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        on_conflict: Some(ConflictPolicy::Skip),
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    let contents = read_to_string(workspace.dir.path().join(TEMPLATE_PATH)).unwrap();
+    assert_eq!(contents, "existing");
+
+    let stderr = String::from_utf8_lossy(workspace.io.stderr());
+    assert!(stderr.contains("README.md (skip)"), "got:\n{}", stderr);
+}
+
+#[test]
+#[serial]
+fn on_conflict_backup_keeps_a_copy() {
+    let mut workspace = TestWorkspace::new();
+    let spec_name = setup_conflict(&workspace, None);
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        on_conflict: Some(ConflictPolicy::Backup),
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    let backup = read_to_string(workspace.dir.path().join("README.md.bak")).unwrap();
+    assert_eq!(backup, "existing");
+
+    let contents = read_to_string(workspace.dir.path().join(TEMPLATE_PATH)).unwrap();
+    assert!(contents.contains("Hello, testing"));
+}
+
+#[test]
+#[serial]
+fn on_conflict_fail_writes_nothing() {
+    let mut workspace = TestWorkspace::new();
+    let spec_name = setup_conflict(&workspace, None);
+
+    let mut spec = workspace.specs.read_spec(&spec_name).unwrap();
+    spec.templates.push(Template {
+        path: PathBuf::from("other.txt"),
+        body: String::from("other"),
+        ..Default::default()
+    });
+    workspace.specs.delete_spec(&spec_name).unwrap();
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        on_conflict: Some(ConflictPolicy::Fail),
+        ..Default::default()
+    };
+
+    assert!(cmd::generate(&workspace.specs, gen_args, &mut workspace.io).is_err());
+    assert!(!workspace.dir.path().join("other.txt").exists());
+}
+
+#[test]
+#[serial]
+fn on_conflict_prompt_without_a_terminal_fails() {
+    let mut workspace = TestWorkspace::new();
+    let spec_name = setup_conflict(&workspace, Some(ConflictPolicy::Prompt));
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        no_input: true,
+        ..Default::default()
+    };
+
+    let err = cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap_err();
+    assert!(err.to_string().contains("no terminal to ask on"), "{}", err);
+
+    let contents = read_to_string(workspace.dir.path().join(TEMPLATE_PATH)).unwrap();
+    assert_eq!(contents, "existing");
+}
+
+#[test]
+#[serial]
+fn template_on_conflict_takes_precedence() {
+    let mut workspace = TestWorkspace::new();
+    let spec_name = setup_conflict(&workspace, Some(ConflictPolicy::Skip));
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        on_conflict: Some(ConflictPolicy::Overwrite),
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    let contents = read_to_string(workspace.dir.path().join(TEMPLATE_PATH)).unwrap();
+    assert_eq!(contents, "existing");
+}
+
+#[test]
+#[serial]
+fn template_paths_are_rendered() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.variables
+        .insert("crate_name".into(), toml::Value::String("widgets".into()));
+    spec.templates.push(Template {
+        path: PathBuf::from("src/{{ crate_name }}/lib.rs"),
+        body: String::from("// {{ crate_name }}"),
+        ..Default::default()
+    });
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    let contents = read_to_string(workspace.dir.path().join("src/widgets/lib.rs")).unwrap();
+    assert_eq!(contents, "// widgets");
+}
+
+#[test]
+#[serial]
+fn rendered_paths_are_checked() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.templates.push(Template {
+        path: PathBuf::from("{{ name }}/escape.txt"),
+        body: String::from("nope"),
+        ..Default::default()
+    });
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        options: vec!["name".into(), "../..".into()],
+        workdir: Some(workspace.dir.path().into()),
+        ..Default::default()
+    };
+
+    assert!(cmd::generate(&workspace.specs, gen_args, &mut workspace.io).is_err());
+}
+
+#[test]
+#[serial]
+fn when_skips_falsy_templates() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.variables
+        .insert("use_ci".into(), toml::Value::Boolean(true));
+    spec.templates.push(Template {
+        path: PathBuf::from("ci.yml"),
+        body: String::from("ci"),
+        when: Some("use_ci".into()),
+        ..Default::default()
+    });
+    spec.templates.push(Template {
+        path: PathBuf::from("Dockerfile"),
+        body: String::from("FROM scratch"),
+        when: Some("name == 'docker'".into()),
+        ..Default::default()
+    });
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        options: vec!["use_ci".into(), "false".into()],
+        workdir: Some(workspace.dir.path().into()),
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    assert!(workspace.dir.path().join(TEMPLATE_PATH).exists());
+    assert!(!workspace.dir.path().join("ci.yml").exists());
+    assert!(!workspace.dir.path().join("Dockerfile").exists());
+}
+
+#[test]
+#[serial]
+fn for_each_items_shadow_variables() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.variables.insert("module".into(), "shadow".into());
+    spec.variables.insert(
+        "modules".into(),
+        toml::Value::Array(vec!["users".into(), "orders".into()]),
+    );
+    spec.templates.push(Template {
+        path: PathBuf::from("out/{{ module }}.rs"),
+        body: String::from("// {{ module }}"),
+        for_each: Some("modules".into()),
+        item_name: Some("module".into()),
+        ..Default::default()
+    });
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    let dir = workspace.dir.path();
+    assert_eq!(
+        read_to_string(dir.join("out/users.rs")).unwrap(),
+        "// users"
+    );
+    assert_eq!(
+        read_to_string(dir.join("out/orders.rs")).unwrap(),
+        "// orders"
+    );
+    assert!(!dir.join("out/shadow.rs").exists());
+}
+
+#[test]
+#[serial]
+fn for_each_generates_one_file_per_item() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.variables.insert(
+        "modules".into(),
+        toml::Value::Array(vec!["users".into(), "orders".into()]),
+    );
+    spec.templates.push(Template {
+        path: PathBuf::from("src/handlers/{{ module }}.rs"),
+        body: String::from("// {{ module }} handler for {{ name }}"),
+        for_each: Some("modules".into()),
+        item_name: Some("module".into()),
+        ..Default::default()
+    });
+    spec.templates.push(Template {
+        path: PathBuf::from("tests/{{ item }}.rs"),
+        body: String::from("// {{ item }}"),
+        for_each: Some("modules".into()),
+        when: Some("item != 'orders'".into()),
+        ..Default::default()
+    });
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    let dir = workspace.dir.path();
+    assert_eq!(
+        read_to_string(dir.join("src/handlers/users.rs")).unwrap(),
+        "// users handler for testing"
+    );
+    assert_eq!(
+        read_to_string(dir.join("src/handlers/orders.rs")).unwrap(),
+        "// orders handler for testing"
+    );
+    assert!(dir.join("tests/users.rs").exists());
+    assert!(!dir.join("tests/orders.rs").exists());
+}
+
+#[test]
+#[serial]
+fn no_input_leaves_empty_variables_alone() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.variables
+        .insert("name".into(), toml::Value::String("".into()));
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        no_input: true,
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    let contents = read_to_string(workspace.dir.path().join(TEMPLATE_PATH)).unwrap();
+    assert_eq!(contents.trim(), "# Template\nHello,");
+}
+
+#[test]
+#[serial]
+fn schema_violations_stop_generation() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.schema.insert(
+        "port".into(),
+        Variable {
+            kind: Some(VariableType::Integer),
+            min: Some(1.0),
+            max: Some(65535.0),
+            default: Some(toml::Value::Integer(8080)),
+            ..Default::default()
+        },
+    );
+    spec.schema.insert(
+        "license".into(),
+        Variable {
+            choices: vec!["MIT".into(), "Apache-2.0".into()],
+            default: Some("MIT".into()),
+            ..Default::default()
+        },
+    );
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        options: vec![
+            "port".into(),
+            "70000".into(),
+            "license".into(),
+            "GPL".into(),
+        ],
+        workdir: Some(workspace.dir.path().into()),
+        no_input: true,
+        ..Default::default()
+    };
+
+    let err = cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap_err();
+    let message = format!("{:#}", err);
+
+    assert!(message.contains("2 variable(s) are invalid"), "{}", message);
+    assert!(!workspace.dir.path().join(TEMPLATE_PATH).exists());
+}
+
+#[test]
+#[serial]
+fn schema_violations_are_reported_before_computing() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.schema.insert(
+        "port".into(),
+        Variable {
+            kind: Some(VariableType::Integer),
+            default: Some(toml::Value::Integer(8080)),
+            ..Default::default()
+        },
+    );
+    spec.schema.insert(
+        "license".into(),
+        Variable {
+            choices: vec!["MIT".into(), "Apache-2.0".into()],
+            default: Some("MIT".into()),
+            ..Default::default()
+        },
+    );
+    spec.computed.insert("next_port".into(), "port + 1".into());
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        options: vec![
+            "port".into(),
+            "eighty".into(),
+            "license".into(),
+            "GPL".into(),
+        ],
+        workdir: Some(workspace.dir.path().into()),
+        no_input: true,
+        ..Default::default()
+    };
+
+    let err = cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap_err();
+    let message = format!("{:#}", err);
+
+    assert!(message.contains("2 variable(s) are invalid"), "{}", message);
+    assert!(message.contains("port:"), "{}", message);
+    assert!(message.contains("license:"), "{}", message);
+    assert!(!message.contains("next_port"), "{}", message);
+}
+
+#[test]
+#[serial]
+fn schema_defaults_are_used() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.variables.remove("name");
+    spec.schema.insert(
+        "name".into(),
+        Variable {
+            kind: Some(VariableType::String),
+            default: Some("schema".into()),
+            ..Default::default()
+        },
+    );
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        no_input: true,
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    let contents = read_to_string(workspace.dir.path().join(TEMPLATE_PATH)).unwrap();
+    assert!(contents.contains("Hello, schema"), "{}", contents);
+}
+
+#[test]
+#[serial]
+fn options_follow_schema_types() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.variables.remove("name");
+    spec.schema.insert(
+        "name".into(),
+        Variable {
+            kind: Some(VariableType::String),
+            ..Default::default()
+        },
+    );
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        options: vec!["name".into(), "1.10".into()],
+        workdir: Some(workspace.dir.path().into()),
+        no_input: true,
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    let contents = read_to_string(workspace.dir.path().join(TEMPLATE_PATH)).unwrap();
+    assert!(contents.contains("Hello, 1.10"), "{}", contents);
+}
+
+#[test]
+#[serial]
+fn inject_into_existing_file_is_idempotent() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.variables.insert(
+        "modules".into(),
+        toml::Value::Array(vec!["users".into(), "orders".into()]),
+    );
+    spec.templates.push(Template {
+        path: PathBuf::from("src/lib.rs"),
+        body: String::from("mod {{ item }};"),
+        for_each: Some("modules".into()),
+        inject: Some(Inject::Before("tmpl:mods".into())),
+        ..Default::default()
+    });
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let lib = workspace.dir.path().join("src/lib.rs");
+    fs::create_dir_all(lib.parent().unwrap()).unwrap();
+    fs::write(&lib, "mod config;\n// tmpl:mods\n").unwrap();
+
+    for _ in 0..2 {
+        let gen_args = GenArgs {
+            names: vec![spec_name.clone()],
+            workdir: Some(workspace.dir.path().into()),
+            on_conflict: Some(ConflictPolicy::Fail),
+            ..Default::default()
+        };
+
+        cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+    }
+
+    assert_eq!(
+        read_to_string(&lib).unwrap(),
+        "mod config;\nmod users;\nmod orders;\n// tmpl:mods\n"
+    );
+}
+
+#[test]
+#[serial]
+fn atomic_writes_nothing_when_a_template_fails() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.templates.push(Template {
+        path: PathBuf::from("broken.txt"),
+        body: String::from("{{ unclosed"),
+        ..Default::default()
+    });
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        atomic: true,
+        ..Default::default()
+    };
+
+    assert!(cmd::generate(&workspace.specs, gen_args, &mut workspace.io).is_err());
+    assert!(!workspace.dir.path().join(TEMPLATE_PATH).exists());
+    assert!(!workspace.dir.path().join("broken.txt").exists());
+}
+
+#[test]
+#[serial]
+fn atomic_restores_overwritten_files_when_a_write_fails() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.templates.push(Template {
+        path: PathBuf::from("blocker/file.txt"),
+        body: String::from("never written"),
+        ..Default::default()
+    });
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    fs::write(workspace.dir.path().join(TEMPLATE_PATH), "existing").unwrap();
+    // a regular file where a directory is needed makes the second write fail
+    fs::write(workspace.dir.path().join("blocker"), "").unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        atomic: true,
+        ..Default::default()
+    };
+
+    assert!(cmd::generate(&workspace.specs, gen_args, &mut workspace.io).is_err());
+
+    let contents = read_to_string(workspace.dir.path().join(TEMPLATE_PATH)).unwrap();
+    assert_eq!(contents, "existing");
+}
+
+#[test]
+#[serial]
+fn atomic_rollback_leaves_no_backups() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.templates.push(Template {
+        path: PathBuf::from("blocker/file.txt"),
+        body: String::from("never written"),
+        ..Default::default()
+    });
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    fs::write(workspace.dir.path().join(TEMPLATE_PATH), "existing").unwrap();
+    fs::write(workspace.dir.path().join("blocker"), "").unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        on_conflict: Some(ConflictPolicy::Backup),
+        atomic: true,
+        ..Default::default()
+    };
+
+    assert!(cmd::generate(&workspace.specs, gen_args, &mut workspace.io).is_err());
+
+    let contents = read_to_string(workspace.dir.path().join(TEMPLATE_PATH)).unwrap();
+    assert_eq!(contents, "existing");
+    assert!(!workspace.dir.path().join("README.md.bak").exists());
+    assert!(!workspace.dir.path().join(".tmpl").exists());
+}
+
+#[cfg(unix)]
+#[test]
+#[serial]
+fn file_modes_are_applied() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.templates.push(Template {
+        path: PathBuf::from("bootstrap.sh"),
+        body: String::from("#!/bin/sh"),
+        executable: true,
+        ..Default::default()
+    });
+    spec.templates.push(Template {
+        path: PathBuf::from("secret.env"),
+        body: String::from("TOKEN="),
+        mode: Some("0600".into()),
+        ..Default::default()
+    });
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    let mode = |path: &str| {
+        fs::metadata(workspace.dir.path().join(path))
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o7777
+    };
+
+    // everyone that can read the script can execute it
+    let script = mode("bootstrap.sh");
+    assert_ne!(script & 0o100, 0);
+    assert_eq!(script & 0o111, (script & 0o444) >> 2);
+    assert_eq!(mode("secret.env"), 0o600);
+}
+
+#[test]
+#[serial]
+fn generate_with_spec_dir() {
+    let mut workspace = TestWorkspace::new();
+    let spec_dir = workspace.dir.path().join("spec");
+    fs::create_dir_all(spec_dir.join("templates/src")).unwrap();
+    fs::write(spec_dir.join("spec.toml"), "[variables]\nname = \"dir\"\n").unwrap();
+    fs::write(
+        spec_dir.join("templates/src/{{ name }}.txt"),
+        "Hello, {{ name }}",
+    )
+    .unwrap();
+
+    let out = workspace.dir.path().join("out");
+    fs::create_dir(&out).unwrap();
+
+    let gen_args = GenArgs {
+        workdir: Some(out.clone()),
+        spec_file: Some(spec_dir),
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    assert_eq!(
+        read_to_string(out.join("src/dir.txt")).unwrap(),
+        "Hello, dir"
+    );
+}
+
+#[test]
+#[serial]
+fn verbatim_templates_are_not_rendered() {
+    let mut workspace = TestWorkspace::new();
+    let spec_dir = workspace.dir.path().join("spec");
+    fs::create_dir_all(spec_dir.join("templates")).unwrap();
+    fs::create_dir_all(spec_dir.join("assets")).unwrap();
+
+    let logo = [0x89, b'P', b'N', b'G', 0xff, 0x00, 0xfe];
+    fs::write(spec_dir.join("assets/logo.png"), logo).unwrap();
+    fs::write(spec_dir.join("templates/icon.bin"), logo).unwrap();
+    fs::write(
+        spec_dir.join("spec.toml"),
+        r#"
+[variables]
+name = "verbatim"
+
+[[templates]]
+path = "logo.png"
+source = "assets/logo.png"
+
+[[templates]]
+path = "hello.bin"
+base64 = "aGVs bG8="
+
+[[templates]]
+path = "raw.txt"
+raw = true
+body = "{{ name }}"
+"#,
+    )
+    .unwrap();
+
+    let out = workspace.dir.path().join("out");
+    fs::create_dir(&out).unwrap();
+
+    let gen_args = GenArgs {
+        workdir: Some(out.clone()),
+        spec_file: Some(spec_dir),
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    assert_eq!(fs::read(out.join("logo.png")).unwrap(), logo);
+    assert_eq!(fs::read(out.join("icon.bin")).unwrap(), logo);
+    assert_eq!(read_to_string(out.join("hello.bin")).unwrap(), "hello");
+    assert_eq!(read_to_string(out.join("raw.txt")).unwrap(), "{{ name }}");
+}
+
+#[test]
+#[serial]
+fn sources_cannot_escape_the_spec() {
+    let mut workspace = TestWorkspace::new();
+    let spec_file = workspace.dir.path().join("spec.toml");
+
+    fs::write(
+        &spec_file,
+        r#"
+[[templates]]
+path = "stolen"
+source = "../../etc/passwd"
+"#,
+    )
+    .unwrap();
+
+    let gen_args = GenArgs {
+        workdir: Some(workspace.dir.path().into()),
+        spec_file: Some(spec_file),
+        ..Default::default()
+    };
+
+    assert!(cmd::generate(&workspace.specs, gen_args, &mut workspace.io).is_err());
+    assert!(!workspace.dir.path().join("stolen").exists());
+}
+
+#[cfg(unix)]
+fn hook(run: &[&str], when: Option<&str>) -> Hook {
+    Hook {
+        run: run.iter().map(|s| s.to_string()).collect(),
+        when: when.map(String::from),
+    }
+}
+
+#[cfg(unix)]
+#[test]
+#[serial]
+fn hooks_run_in_order_when_trusted() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.hooks.post = vec![
+        hook(&["sh", "-c", "echo first {{ name }} >> hooks.log"], None),
+        hook(&["sh", "-c", "echo skipped >> hooks.log"], Some("false")),
+        hook(&["sh", "-c", "cat README.md >> hooks.log"], None),
+    ];
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        run_hooks: true,
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    // hooks run in the output directory, after the templates are written
+    let log = read_to_string(workspace.dir.path().join("hooks.log")).unwrap();
+    assert_eq!(log, "first testing\n# Template\nHello, testing");
+}
+
+#[cfg(unix)]
+#[test]
+#[serial]
+fn hooks_need_to_be_trusted() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.hooks.post = vec![hook(&["touch", "hooked"], None)];
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        no_input: true,
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    assert!(workspace.dir.path().join(TEMPLATE_PATH).exists());
+    assert!(!workspace.dir.path().join("hooked").exists());
+}
+
+#[cfg(unix)]
+#[test]
+#[serial]
+fn untrusted_pre_hooks_stop_generation() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.hooks.pre = vec![hook(&["false"], None)];
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name.clone()],
+        workdir: Some(workspace.dir.path().into()),
+        no_input: true,
+        ..Default::default()
+    };
+
+    assert!(cmd::generate(&workspace.specs, gen_args, &mut workspace.io).is_err());
+    assert!(!workspace.dir.path().join(TEMPLATE_PATH).exists());
+
+    // unless skipping them is asked for
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        no_input: true,
+        skip_hooks: true,
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+    assert!(workspace.dir.path().join(TEMPLATE_PATH).exists());
+}
+
+#[cfg(unix)]
+#[test]
+#[serial]
+fn failing_hooks_are_reported() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.hooks.post = vec![hook(&["false"], None), hook(&["touch", "hooked"], None)];
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        run_hooks: true,
+        ..Default::default()
+    };
+
+    assert!(cmd::generate(&workspace.specs, gen_args, &mut workspace.io).is_err());
+
+    // the templates were still written, but nothing after the failed hook ran
+    assert!(workspace.dir.path().join(TEMPLATE_PATH).exists());
+    assert!(!workspace.dir.path().join("hooked").exists());
+}
+
+#[cfg(unix)]
+#[test]
+#[serial]
+fn failing_pre_hooks_stop_generation() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.hooks.pre = vec![hook(&["true"], None), hook(&["test", "-d", ".git"], None)];
+    spec.hooks.post = vec![hook(&["touch", "hooked"], None)];
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name.clone()],
+        workdir: Some(workspace.dir.path().into()),
+        run_hooks: true,
+        ..Default::default()
+    };
+
+    assert!(cmd::generate(&workspace.specs, gen_args, &mut workspace.io).is_err());
+    assert!(!workspace.dir.path().join(TEMPLATE_PATH).exists());
+    assert!(!workspace.dir.path().join("hooked").exists());
+
+    // once the precondition holds, generation goes ahead
+    fs::create_dir(workspace.dir.path().join(".git")).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        run_hooks: true,
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+    assert!(workspace.dir.path().join(TEMPLATE_PATH).exists());
+    assert!(workspace.dir.path().join("hooked").exists());
+}
+
+#[test]
+#[serial]
+fn templates_can_include_shared_partials() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let partials = workspace.specs.partials_dir();
+    fs::create_dir_all(partials.join("licenses")).unwrap();
+    fs::write(partials.join("licenses/mit.txt"), "MIT License, {{ name }}").unwrap();
+    fs::write(
+        partials.join("macros.jinja"),
+        "{% macro shout(text) %}{{ text | upper }}!{% endmacro %}",
+    )
+    .unwrap();
+
+    let mut spec = create_test_spec();
+    spec.templates.push(Template {
+        path: PathBuf::from("LICENSE"),
+        body: String::from(
+            "{% include \"licenses/mit.txt\" %}\n\
+             {% import \"macros.jinja\" as m %}{{ m.shout(name) }}",
+        ),
+        ..Default::default()
+    });
+    spec.templates.push(Template {
+        path: PathBuf::from("missing.txt"),
+        body: String::from("{% include \"nope.txt\" %}"),
+        ..Default::default()
+    });
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        ..Default::default()
+    };
+
+    // the template including a partial that doesn't exist fails on its own
+    assert!(cmd::generate(&workspace.specs, gen_args, &mut workspace.io).is_err());
+    assert!(!workspace.dir.path().join("missing.txt").exists());
+
+    let license = read_to_string(workspace.dir.path().join("LICENSE")).unwrap();
+    assert_eq!(license, "MIT License, testing\nTESTING!");
+}
+
+#[test]
+#[serial]
+fn extended_specs_are_generated_with_their_parents() {
+    let mut workspace = TestWorkspace::new();
+
+    // the parent is a directory spec, with a file that has to be copied from its own directory
+    let base = workspace.specs.dir().join("base");
+    fs::create_dir_all(base.join("templates")).unwrap();
+    fs::write(base.join("spec.toml"), "[variables]\nname = \"base\"\n").unwrap();
+    fs::write(base.join("templates/README.md"), "Hello, {{ name }}").unwrap();
+    fs::write(base.join("templates/icon.bin"), [0xff, 0x00, 0xfe]).unwrap();
+
+    let spec_name = OsString::from("child.spec");
+    let mut spec = create_test_spec();
+    spec.extends = Some("base".into());
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    let readme = read_to_string(workspace.dir.path().join(TEMPLATE_PATH)).unwrap();
+    assert_eq!(readme, "# Template\nHello, testing");
+
+    let icon = fs::read(workspace.dir.path().join("icon.bin")).unwrap();
+    assert_eq!(icon, [0xff, 0x00, 0xfe]);
+}
+
+#[test]
+#[serial]
+fn several_specs_are_generated_together() {
+    let mut workspace = TestWorkspace::new();
+
+    let base = OsString::from("base.spec");
+    workspace
+        .specs
+        .write_spec(&base, &create_test_spec())
+        .unwrap();
+
+    let ci = OsString::from("ci.spec");
+    let mut spec = Spec::default();
+    spec.variables
+        .insert("name".into(), toml::Value::String("ci".into()));
+    spec.variables
+        .insert("runner".into(), toml::Value::String("ubuntu".into()));
+    spec.templates.push(Template {
+        path: PathBuf::from("ci.yml"),
+        body: String::from("{{ name }} on {{ runner }}"),
+        ..Default::default()
+    });
+    workspace.specs.write_spec(&ci, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![base.clone(), ci.clone()],
+        workdir: Some(workspace.dir.path().into()),
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    // later specs take precedence when they set the same variable
+    let readme = read_to_string(workspace.dir.path().join(TEMPLATE_PATH)).unwrap();
+    assert_eq!(readme, "# Template\nHello, ci");
+    let ci_yml = read_to_string(workspace.dir.path().join("ci.yml")).unwrap();
+    assert_eq!(ci_yml, "ci on ubuntu");
+}
+
+#[test]
+#[serial]
+fn specs_generating_the_same_path_are_rejected() {
+    let mut workspace = TestWorkspace::new();
+
+    let first = OsString::from("first.spec");
+    let second = OsString::from("second.spec");
+    workspace
+        .specs
+        .write_spec(&first, &create_test_spec())
+        .unwrap();
+    workspace
+        .specs
+        .write_spec(&second, &create_test_spec())
+        .unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![first, second],
+        workdir: Some(workspace.dir.path().into()),
+        ..Default::default()
+    };
+
+    let err = cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap_err();
+    let err = format!("{:#}", err);
+    assert!(
+        err.contains("README.md (first.spec, second.spec)"),
+        "{}",
+        err
+    );
+    assert!(!workspace.dir.path().join(TEMPLATE_PATH).exists());
+}
+
+#[test]
+#[serial]
+fn specs_rendering_the_same_path_are_rejected() {
+    let mut workspace = TestWorkspace::new();
+
+    let first = OsString::from("first.spec");
+    let second = OsString::from("second.spec");
+
+    let mut spec = create_test_spec();
+    spec.templates = vec![Template {
+        path: PathBuf::from("{{ name }}.txt"),
+        body: String::from("first"),
+        ..Default::default()
+    }];
+    workspace.specs.write_spec(&first, &spec).unwrap();
+
+    spec.templates = vec![Template {
+        path: PathBuf::from("testing.txt"),
+        body: String::from("second"),
+        ..Default::default()
+    }];
+    workspace.specs.write_spec(&second, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![first, second],
+        workdir: Some(workspace.dir.path().into()),
+        ..Default::default()
+    };
+
+    let err = cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap_err();
+    let err = format!("{:#}", err);
+    assert!(
+        err.contains("testing.txt ({{ name }}.txt, testing.txt)"),
+        "{}",
+        err
+    );
+    assert!(!workspace.dir.path().join("testing.txt").exists());
+}
+
+#[test]
+#[serial]
+fn computed_variables_are_derived_from_other_variables() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.computed
+        .insert("module".into(), "name | snake_case".into());
+    spec.computed
+        .insert("module_path".into(), "'src/' ~ module ~ '.rs'".into());
+    spec.templates.push(Template {
+        path: PathBuf::from("{{ module_path }}"),
+        body: String::from("mod {{ module }};"),
+        ..Default::default()
+    });
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        options: vec!["name".into(), "User Accounts".into()],
+        workdir: Some(workspace.dir.path().into()),
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    let module = read_to_string(workspace.dir.path().join("src/user_accounts.rs")).unwrap();
+    assert_eq!(module, "mod user_accounts;");
+}
+
+#[test]
+#[serial]
+fn vars_files_are_layered_between_defaults_and_options() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.variables
+        .insert("greeting".into(), toml::Value::String("Hello".into()));
+    spec.variables
+        .insert("punctuation".into(), toml::Value::String(".".into()));
+    spec.templates[0].body = String::from("{{ greeting }}, {{ name }}{{ punctuation }}");
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    let toml_file = workspace.dir.path().join("vars.toml");
+    fs::write(&toml_file, "greeting = \"Hi\"\nname = \"toml\"\n").unwrap();
+    let yaml_file = workspace.dir.path().join("vars.yaml");
+    fs::write(&yaml_file, "name: yaml\npunctuation: '?'\n").unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        vars_files: vec![toml_file, yaml_file],
+        options: vec!["punctuation".into(), "!".into()],
+        workdir: Some(workspace.dir.path().into()),
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    let contents = read_to_string(workspace.dir.path().join(TEMPLATE_PATH)).unwrap();
+    assert_eq!(contents, "Hi, yaml!");
+}
+
+#[test]
+#[serial]
+fn environment_variables_override_defaults_but_not_options() {
+    let spec_name = OsString::from("test.spec");
+    let mut workspace = TestWorkspace::new();
+
+    let mut spec = create_test_spec();
+    spec.variables
+        .insert("count".into(), toml::Value::Integer(1));
+    spec.variables
+        .insert("version".into(), toml::Value::String("1.0".into()));
+    spec.templates[0].body = String::from("{{ name }} {{ count + 1 }} {{ version }}");
+    workspace.specs.write_spec(&spec_name, &spec).unwrap();
+
+    // SAFETY: every test in this file runs serially, so nothing else touches the environment
+    unsafe {
+        std::env::set_var("TMPL_TEST_ENV_name", "from-env");
+        std::env::set_var("TMPL_TEST_ENV_count", "41");
+        std::env::set_var("TMPL_TEST_ENV_version", "1.10");
+    }
+
+    let gen_args = GenArgs {
+        names: vec![spec_name.clone()],
+        env_prefix: Some("TMPL_TEST_ENV_".into()),
+        workdir: Some(workspace.dir.path().into()),
+        ..Default::default()
+    };
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    // values from the environment keep the type of their default
+    let contents = read_to_string(workspace.dir.path().join(TEMPLATE_PATH)).unwrap();
+    assert_eq!(contents, "from-env 42 1.10");
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        env_prefix: Some("TMPL_TEST_ENV_".into()),
+        options: vec!["name".into(), "from-option".into()],
+        workdir: Some(workspace.dir.path().into()),
+        ..Default::default()
+    };
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    unsafe {
+        std::env::remove_var("TMPL_TEST_ENV_name");
+        std::env::remove_var("TMPL_TEST_ENV_count");
+        std::env::remove_var("TMPL_TEST_ENV_version");
+    }
+
+    let contents = read_to_string(workspace.dir.path().join(TEMPLATE_PATH)).unwrap();
+    assert_eq!(contents, "from-option 42 1.10");
+}
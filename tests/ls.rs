@@ -14,6 +14,7 @@ fn create_test_spec() -> Spec {
     let mut spec = Spec {
         variables: toml::map::Map::new(),
         templates: vec![],
+        ..Default::default()
     };
 
     spec.variables.insert("name".into(), "world".into());
@@ -21,6 +22,7 @@ fn create_test_spec() -> Spec {
     spec.templates.push(Template {
         path: "README.md".into(),
         body: "Hello, {{ name }}".into(),
+        ..Default::default()
     });
 
     spec
@@ -0,0 +1,146 @@
+/// Tests the undo subcommand. Like the generate tests, these change the current working directory
+/// while generating, so they have to run serially.
+mod common;
+
+use std::ffi::OsString;
+use std::fs;
+
+use serial_test::serial;
+use tmpl::cli::{GenArgs, UndoArgs};
+use tmpl::cmd;
+use tmpl::specs::{ConflictPolicy, Spec, Template};
+
+use crate::common::TestWorkspace;
+
+fn create_test_spec() -> Spec {
+    let mut spec = Spec {
+        variables: toml::map::Map::new(),
+        templates: Vec::new(),
+        ..Default::default()
+    };
+
+    spec.templates.push(Template {
+        path: "README.md".into(),
+        body: "generated".into(),
+        ..Default::default()
+    });
+
+    spec.templates.push(Template {
+        path: "src/main.rs".into(),
+        body: "fn main() {}".into(),
+        ..Default::default()
+    });
+
+    spec
+}
+
+fn generate(workspace: &mut TestWorkspace) {
+    let spec_name = OsString::from("test.spec");
+    workspace
+        .specs
+        .write_spec(&spec_name, &create_test_spec())
+        .unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(workspace.dir.path().into()),
+        ..Default::default()
+    };
+
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+}
+
+#[test]
+#[serial]
+fn undo_removes_backups() {
+    let mut workspace = TestWorkspace::new();
+    let dir = workspace.dir.path().to_path_buf();
+    fs::write(dir.join("README.md"), "original").unwrap();
+
+    let spec_name = OsString::from("test.spec");
+    workspace
+        .specs
+        .write_spec(&spec_name, &create_test_spec())
+        .unwrap();
+
+    let gen_args = GenArgs {
+        names: vec![spec_name],
+        workdir: Some(dir.clone()),
+        on_conflict: Some(ConflictPolicy::Backup),
+        ..Default::default()
+    };
+    cmd::generate(&workspace.specs, gen_args, &mut workspace.io).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(dir.join("README.md.bak")).unwrap(),
+        "original"
+    );
+
+    let args = UndoArgs {
+        workdir: Some(dir.clone()),
+    };
+    cmd::undo(args, &mut workspace.io).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(dir.join("README.md")).unwrap(),
+        "original"
+    );
+    assert!(!dir.join("README.md.bak").exists());
+    assert!(!dir.join(".tmpl").exists());
+}
+
+#[test]
+#[serial]
+fn undo_reverts_generation() {
+    let mut workspace = TestWorkspace::new();
+    let dir = workspace.dir.path().to_path_buf();
+    fs::write(dir.join("README.md"), "original").unwrap();
+
+    generate(&mut workspace);
+    assert_eq!(
+        fs::read_to_string(dir.join("README.md")).unwrap(),
+        "generated"
+    );
+
+    let args = UndoArgs {
+        workdir: Some(dir.clone()),
+    };
+    cmd::undo(args, &mut workspace.io).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(dir.join("README.md")).unwrap(),
+        "original"
+    );
+    assert!(!dir.join("src").exists());
+    assert!(!dir.join(".tmpl").exists());
+}
+
+#[test]
+#[serial]
+fn undo_refuses_to_touch_changed_files() {
+    let mut workspace = TestWorkspace::new();
+    let dir = workspace.dir.path().to_path_buf();
+
+    generate(&mut workspace);
+    fs::write(dir.join("src/main.rs"), "fn main() { println!(\"mine\"); }").unwrap();
+
+    let args = UndoArgs {
+        workdir: Some(dir.clone()),
+    };
+    assert!(cmd::undo(args, &mut workspace.io).is_err());
+
+    assert!(!dir.join("README.md").exists());
+    assert!(dir.join("src/main.rs").exists());
+
+    let stderr = String::from_utf8_lossy(workspace.io.stderr());
+    assert!(stderr.contains("main.rs"), "got:\n{}", stderr);
+}
+
+#[test]
+fn undo_without_manifest_returns_error() {
+    let mut workspace = TestWorkspace::new();
+    let args = UndoArgs {
+        workdir: Some(workspace.dir.path().into()),
+    };
+    assert!(cmd::undo(args, &mut workspace.io).is_err());
+}